@@ -0,0 +1,51 @@
+//! Compares how fast the cracker scans a window of masters when the threads
+//! take every nth master versus a contiguous range each
+//!
+//! Run with `cargo bench --bench partitioning`.
+
+use std::{hint::black_box, thread, time::Instant};
+
+use vault_crypt::{
+    pins::Pins,
+    re::{Cracker, Partition, ScoreMode},
+};
+
+/// Masters scanned per run, small enough to finish in seconds
+const WINDOW: u32 = 4_000_000;
+const RUNS: u32 = 3;
+
+fn main() {
+    let mut pins = Pins::new(4_596_769);
+    for pin in [
+        524_422_338,
+        987_654_321,
+        70_460_004,
+        124_076_109,
+        769_626_996,
+    ] {
+        pins.add(pin);
+    }
+    let cracker = Cracker::load(&pins.save().unwrap()).unwrap();
+    let thread_count = thread::available_parallelism().map_or(4, |n| n.get() as u32);
+    println!("{WINDOW} masters, {thread_count} thread(s), best of {RUNS} runs");
+    for (name, partition) in [
+        ("strided", Partition::Strided),
+        ("contiguous", Partition::Contiguous),
+    ] {
+        let best = (0..RUNS)
+            .map(|_| {
+                let start = Instant::now();
+                black_box(cracker.bruteforce_window(
+                    thread_count,
+                    ScoreMode::CommonPins,
+                    0..WINDOW,
+                    partition,
+                ));
+                start.elapsed()
+            })
+            .min()
+            .unwrap();
+        let rate = WINDOW as f64 / best.as_secs_f64() / 1e6;
+        println!("{name:>10}: {best:.2?} ({rate:.1}M masters/s)");
+    }
+}
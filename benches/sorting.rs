@@ -0,0 +1,74 @@
+//! Compares sorting all scan results at the end against sorting them per
+//! thread and merging, which is what the cracker does
+//!
+//! Run with `cargo bench --bench sorting`.
+
+use std::{hint::black_box, thread, time::Instant};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use vault_crypt::re::{self, SusMaster};
+
+/// Results per thread, about what a validity-only scan of a big vault finds
+const RESULTS_PER_THREAD: usize = 1_000_000;
+const THREADS: usize = 8;
+const RUNS: u32 = 3;
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let parts: Vec<Vec<SusMaster>> = (0..THREADS)
+        .map(|i| {
+            (0..RESULTS_PER_THREAD)
+                .map(|j| SusMaster {
+                    master: (j * THREADS + i) as u32,
+                    score: rng.gen_range(1..=5),
+                })
+                .collect()
+        })
+        .collect();
+    println!("{THREADS} thread(s) of {RESULTS_PER_THREAD} results, best of {RUNS} runs");
+    let sort_at_end = || {
+        let mut all: Vec<_> = parts
+            .iter()
+            .flat_map(|part| part.iter().map(copy))
+            .collect();
+        all.sort_by_key(re::rank);
+        all
+    };
+    let sort_and_merge = || {
+        let sorted = thread::scope(|scope| {
+            let handles: Vec<_> = parts
+                .iter()
+                .map(|part| {
+                    scope.spawn(|| {
+                        let mut part: Vec<_> = part.iter().map(copy).collect();
+                        part.sort_by_key(re::rank);
+                        part
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        re::merge_ranked(sorted)
+    };
+    for (name, run) in [
+        ("sort at end", &sort_at_end as &dyn Fn() -> Vec<SusMaster>),
+        ("sort and merge", &sort_and_merge),
+    ] {
+        let best = (0..RUNS)
+            .map(|_| {
+                let start = Instant::now();
+                black_box(run());
+                start.elapsed()
+            })
+            .min()
+            .unwrap();
+        println!("{name:>14}: {best:.2?}");
+    }
+}
+
+fn copy(sus: &SusMaster) -> SusMaster {
+    SusMaster {
+        master: sus.master,
+        score: sus.score,
+    }
+}
@@ -0,0 +1,95 @@
+//! System clipboard access through the platform's command line tools
+//!
+//! `wl-copy`/`wl-paste` are used on Wayland, `xclip` on X11 and
+//! `pbcopy`/`pbpaste` on macOS.
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use anyhow::{bail, Context, Result};
+
+pub struct Clipboard {
+    copy: &'static [&'static str],
+    paste: &'static [&'static str],
+}
+
+impl Clipboard {
+    /// Finds the clipboard tools for the current session, if it has any and
+    /// they are installed
+    pub fn detect() -> Option<Self> {
+        Self::for_session()
+            .filter(|clipboard| on_path(clipboard.copy[0]) && on_path(clipboard.paste[0]))
+    }
+
+    /// The clipboard tools the current session would use
+    fn for_session() -> Option<Self> {
+        let has = |var| std::env::var_os(var).is_some_and(|v| !v.is_empty());
+        if has("WAYLAND_DISPLAY") {
+            Some(Self {
+                copy: &["wl-copy"],
+                paste: &["wl-paste", "--no-newline"],
+            })
+        } else if has("DISPLAY") {
+            Some(Self {
+                copy: &["xclip", "-selection", "clipboard"],
+                paste: &["xclip", "-selection", "clipboard", "-o"],
+            })
+        } else if cfg!(target_os = "macos") {
+            Some(Self {
+                copy: &["pbcopy"],
+                paste: &["pbpaste"],
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn copy(&self, text: &str) -> Result<()> {
+        let mut child = Command::new(self.copy[0])
+            .args(&self.copy[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Could not run {}", self.copy[0]))?;
+        child
+            .stdin
+            .take()
+            .expect("Stdin should be piped")
+            .write_all(text.as_bytes())?;
+        if !child.wait()?.success() {
+            bail!("{} failed", self.copy[0]);
+        }
+        Ok(())
+    }
+
+    pub fn paste(&self) -> Result<String> {
+        let output = Command::new(self.paste[0])
+            .args(&self.paste[1..])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .with_context(|| format!("Could not run {}", self.paste[0]))?;
+        if !output.status.success() {
+            bail!("{} failed", self.paste[0]);
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Empties the clipboard if it still holds `text`, so that something
+    /// copied since is left alone
+    pub fn clear_if(&self, text: &str) -> Result<()> {
+        if self.paste()? == text {
+            self.copy("")?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether `program` is a file in one of the directories of `PATH`
+fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
@@ -0,0 +1,27 @@
+use std::{error, fmt};
+
+/// Errors that callers may want to tell apart from other failures
+#[derive(Debug)]
+pub enum VaultError {
+    /// The bytes do not have the structure of a vault
+    Malformed(String),
+    /// The master does not decrypt the vault
+    WrongMaster,
+}
+
+impl VaultError {
+    pub fn malformed(reason: impl Into<String>) -> Self {
+        Self::Malformed(reason.into())
+    }
+}
+
+impl fmt::Display for VaultError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed(reason) => write!(f, "Malformed vault: {reason}"),
+            Self::WrongMaster => write!(f, "Wrong master pin"),
+        }
+    }
+}
+
+impl error::Error for VaultError {}
@@ -0,0 +1,62 @@
+use crate::pins::Pins;
+
+/// Columns expected by the KeePass/KeePassXC CSV importer
+const KEEPASS_HEADER: [&str; 5] = ["Title", "Username", "Password", "URL", "Notes"];
+
+#[derive(Clone, Copy)]
+pub enum ExportFormat {
+    KeepassCsv,
+}
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "keepass-csv" => Some(Self::KeepassCsv),
+            _ => None,
+        }
+    }
+}
+
+pub fn export(pins: &Pins, format: ExportFormat) -> String {
+    match format {
+        ExportFormat::KeepassCsv => keepass_csv(pins),
+    }
+}
+
+/// Writes a row per pin or text secret, leaving out empty entries
+pub fn keepass_csv(pins: &Pins) -> String {
+    let mut out = String::new();
+    write_csv_row(&mut out, &KEEPASS_HEADER);
+    for pin in pins.iter() {
+        let title = format!("Pin {}", pin.id);
+        let password = match pins.text(pin.id) {
+            Some("") => continue,
+            Some(text) => text.to_string(),
+            None if pin.pin == 0 => continue,
+            None => pin.pin.to_string(),
+        };
+        write_csv_row(&mut out, &[&title, "", &password, "", ""]);
+    }
+    out
+}
+
+fn write_csv_row(out: &mut String, fields: &[&str]) {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_csv_field(out, field);
+    }
+    out.push_str("\r\n");
+}
+
+/// Writes a field, quoting it as described in RFC 4180 if necessary
+fn write_csv_field(out: &mut String, field: &str) {
+    if field.contains([',', '"', '\r', '\n']) {
+        out.push('"');
+        out.push_str(&field.replace('"', "\"\""));
+        out.push('"');
+    } else {
+        out.push_str(field);
+    }
+}
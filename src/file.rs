@@ -0,0 +1,109 @@
+use std::{
+    borrow::Cow,
+    env,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Replaces the file at `path` with `bytes` without ever leaving it half
+/// written
+///
+/// The bytes are written to a temporary file next to `path`, which is then
+/// renamed over it. The permissions of an existing file are kept, and on Unix
+/// a new file is only readable by the user.
+pub fn write_atomic(path: impl AsRef<Path>, bytes: &[u8]) -> io::Result<()> {
+    let path = path.as_ref();
+    let temp = temp_path(path)?;
+    let mut options = OpenOptions::new();
+    // Never opens a file that someone else put there
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(&temp)?;
+    let result = (|| {
+        // Before writing, so the bytes are never more readable than the vault
+        if let Ok(metadata) = fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        fs::rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Hidden file in the same directory as `path`, so renaming stays atomic
+fn temp_path(path: &Path) -> io::Result<PathBuf> {
+    let Some(name) = path.file_name() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Not a file path: {}", path.display()),
+        ));
+    };
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(name);
+    temp_name.push(format!(".{}.tmp", process::id()));
+    Ok(path.with_file_name(temp_name))
+}
+
+/// Path of the `n`th most recent backup of `path`, starting at 1
+pub fn backup_path(path: impl AsRef<Path>, n: usize) -> PathBuf {
+    let path = path.as_ref();
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".bak.{n}"));
+    PathBuf::from(name)
+}
+
+/// Copies the file at `path` to its first backup, shifting older backups
+/// back and keeping at most `count` of them
+///
+/// Does nothing if `count` is 0 or there is no file at `path` yet.
+pub fn rotate_backups(path: impl AsRef<Path>, count: usize) -> io::Result<()> {
+    let path = path.as_ref();
+    if count == 0 || !path.exists() {
+        return Ok(());
+    }
+    for n in (1..count).rev() {
+        let backup = backup_path(path, n);
+        if backup.exists() {
+            fs::rename(&backup, backup_path(path, n + 1))?;
+        }
+    }
+    fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+/// Expands a leading `~` or `~user` to that home directory, as a shell would
+/// outside of quotes
+///
+/// Paths without one, and ones naming a user whose home is unknown, are
+/// returned as they are.
+pub fn expand_home(path: &str) -> Cow<'_, str> {
+    let Some(rest) = path.strip_prefix('~') else {
+        return Cow::Borrowed(path);
+    };
+    let (user, rest) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let home = if user.is_empty() {
+        env::var("HOME").ok().filter(|home| !home.is_empty())
+    } else {
+        user_home(user)
+    };
+    match home {
+        Some(home) => Cow::Owned(format!("{}{rest}", home.trim_end_matches('/'))),
+        None => Cow::Borrowed(path),
+    }
+}
+
+/// Home directory of `user` according to `/etc/passwd`
+fn user_home(user: &str) -> Option<String> {
+    let passwd = fs::read_to_string("/etc/passwd").ok()?;
+    passwd.lines().find_map(|line| {
+        let fields: Vec<_> = line.split(':').collect();
+        (fields.len() >= 6 && fields[0] == user).then(|| fields[5].to_string())
+    })
+}
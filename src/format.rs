@@ -0,0 +1,251 @@
+//! Layout of vault files
+//!
+//! `VaultFile::parse` is the one place that reads the structure of a vault.
+//! It checks every length and id but decrypts nothing, which is left to
+//! `Pins::load` and the cracker.
+
+use std::collections::HashSet;
+
+use anyhow::{bail, Result};
+
+use crate::error::VaultError;
+
+/// First byte of a versioned vault, which no legacy pin count can reach
+pub(crate) const VERSION_MARKER: u8 = 0xFF;
+/// A pin count followed by the pins, without any header
+pub const VERSION_LEGACY: u8 = 1;
+/// Marker and version, the pins, then a length prefixed metadata block
+pub const VERSION_METADATA: u8 = 2;
+/// Marker and version, then two tagged sections that each hold a vault in the
+/// text format, see `Pins::add_duress`
+pub const VERSION_DURESS: u8 = 3;
+/// The metadata format followed by a length prefixed block of previous pin
+/// values, which may be left out at the end of a duress section
+pub const VERSION_HISTORY: u8 = 4;
+/// The history format followed by a length prefixed block of text secrets,
+/// which may be left out like the history block
+pub const VERSION_TEXTS: u8 = 5;
+/// The text secret format followed by a length prefixed block of expiry
+/// times, which may be left out like the other blocks
+pub const VERSION_EXPIRY: u8 = 6;
+/// The expiry format followed by a length prefixed block of the times pins
+/// were last used, which may be left out like the other blocks
+pub const VERSION_USAGE: u8 = 7;
+
+/// Format of the sections of a duress vault
+///
+/// Sections written by earlier versions are in the history, text secret or
+/// expiry format, which read the same since all trailing blocks are optional.
+pub(crate) const SECTION_VERSION: u8 = VERSION_USAGE;
+
+/// Bytes of a pin record, the id followed by the encrypted pin
+///
+/// History entries share the layout.
+pub(crate) const RECORD_SIZE: usize = 5;
+const _: () = assert!(RECORD_SIZE == size_of::<u8>() + size_of::<u32>());
+
+/// Bytes of an expiry or last use entry, the id followed by Unix seconds
+pub(crate) const TIMESTAMP_SIZE: usize = size_of::<u8>() + size_of::<u64>();
+
+/// A vault split into its parts
+pub struct VaultFile<'a> {
+    pub version: u8,
+    /// The body of the vault, or both sections of a duress vault in the order
+    /// they are stored
+    pub sections: Vec<Section<'a>>,
+}
+
+/// The pins and blocks of a vault or of one section of a duress vault
+pub struct Section<'a> {
+    /// Tag of the master that opens the section of a duress vault
+    pub tag: Option<u8>,
+    /// All bytes of the section, starting at the pin count
+    pub bytes: &'a [u8],
+    pub records: Vec<Record>,
+    /// Encrypted blocks, if the section has them
+    pub metadata: Option<&'a [u8]>,
+    pub history: Option<&'a [u8]>,
+    pub texts: Option<&'a [u8]>,
+    pub expiry: Option<&'a [u8]>,
+    pub last_used: Option<&'a [u8]>,
+}
+
+/// A pin as stored, still encrypted
+#[derive(Clone, Copy)]
+pub struct Record {
+    pub id: u8,
+    pub pin: u32,
+}
+
+impl Record {
+    /// Reads a record from bytes of exactly `RECORD_SIZE`
+    pub(crate) fn read(bytes: &[u8]) -> Self {
+        let (id, pin) = bytes.split_at(size_of::<u8>());
+        Self {
+            id: id[0],
+            pin: u32::from_be_bytes(pin.try_into().expect("Record should hold a whole pin")),
+        }
+    }
+}
+
+impl<'a> VaultFile<'a> {
+    /// Parses and checks the structure of a vault, down to the last byte
+    ///
+    /// Fails with `VaultError::Malformed` naming the first part that does not
+    /// fit, including bytes left over after the last block.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self> {
+        let (version, bytes) = split_version(bytes)?;
+        if version != VERSION_DURESS {
+            return Ok(Self {
+                version,
+                sections: vec![Section::parse(bytes, version, None)?],
+            });
+        }
+        let mut rest = bytes;
+        let mut sections = Vec::new();
+        for _ in 0..2 {
+            let [tag, a, b, c, d, body @ ..] = rest else {
+                bail!(VaultError::malformed("Missing section header"));
+            };
+            let len = u32::from_be_bytes([*a, *b, *c, *d]) as usize;
+            if body.len() < len {
+                bail!(VaultError::malformed("Not enough bytes for section length"));
+            }
+            let (body, next) = body.split_at(len);
+            sections.push(Section::parse(body, SECTION_VERSION, Some(*tag))?);
+            rest = next;
+        }
+        if sections[0].tag == sections[1].tag {
+            bail!(VaultError::malformed("Both sections have the same tag"));
+        }
+        check_trailing(rest.len(), "the sections")?;
+        Ok(Self { version, sections })
+    }
+}
+
+impl<'a> Section<'a> {
+    /// Parses the bytes of a vault in `version` starting at the pin count
+    fn parse(bytes: &'a [u8], version: u8, tag: Option<u8>) -> Result<Self> {
+        let mut section = Self {
+            tag,
+            bytes,
+            records: Vec::new(),
+            metadata: None,
+            history: None,
+            texts: None,
+            expiry: None,
+            last_used: None,
+        };
+        let [len, rest @ ..] = bytes else {
+            bail!(VaultError::malformed("Missing pin count"));
+        };
+        let len = *len as usize;
+        if rest.len() < len * RECORD_SIZE {
+            bail!(VaultError::malformed("Not enough bytes for given length"));
+        }
+        let (records, rest) = rest.split_at(len * RECORD_SIZE);
+        let mut ids = HashSet::new();
+        for record in records.chunks_exact(RECORD_SIZE) {
+            let Record { id, pin } = Record::read(record);
+            if id > 99 {
+                bail!(VaultError::malformed(format!("Id is too large: {id} > 99")));
+            }
+            if !ids.insert(id) {
+                bail!(VaultError::malformed(format!("Duplicate id: {id}")));
+            }
+            section.records.push(Record { id, pin });
+        }
+        if version < VERSION_METADATA {
+            check_trailing(rest.len(), "the pins")?;
+            return Ok(section);
+        }
+        let (metadata, rest) = split_block(rest, "metadata")?;
+        section.metadata = Some(metadata);
+        if version < VERSION_HISTORY || rest.is_empty() {
+            check_trailing(rest.len(), "the metadata")?;
+            return Ok(section);
+        }
+        let (history, rest) = split_block(rest, "history")?;
+        if !history.len().is_multiple_of(RECORD_SIZE) {
+            bail!(VaultError::malformed(
+                "History is not made of whole entries"
+            ));
+        }
+        section.history = Some(history);
+        if version < VERSION_TEXTS || rest.is_empty() {
+            check_trailing(rest.len(), "the history")?;
+            return Ok(section);
+        }
+        let (texts, rest) = split_block(rest, "text secrets")?;
+        section.texts = Some(texts);
+        if version < VERSION_EXPIRY || rest.is_empty() {
+            check_trailing(rest.len(), "the text secrets")?;
+            return Ok(section);
+        }
+        let (expiry, rest) = split_timestamps(rest, "expiry times")?;
+        section.expiry = Some(expiry);
+        if version < VERSION_USAGE || rest.is_empty() {
+            check_trailing(rest.len(), "the expiry times")?;
+            return Ok(section);
+        }
+        let (last_used, rest) = split_timestamps(rest, "last use times")?;
+        section.last_used = Some(last_used);
+        check_trailing(rest.len(), "the last use times")?;
+        Ok(section)
+    }
+}
+
+/// Splits off the header of versioned vaults, returning the format version
+/// and the bytes starting at the pin count
+pub(crate) fn split_version(bytes: &[u8]) -> Result<(u8, &[u8])> {
+    match bytes {
+        [] => bail!(VaultError::malformed("Input is empty")),
+        [VERSION_MARKER] => bail!(VaultError::malformed("Missing format version")),
+        [VERSION_MARKER, VERSION_METADATA, bytes @ ..] => Ok((VERSION_METADATA, bytes)),
+        [VERSION_MARKER, VERSION_DURESS, bytes @ ..] => Ok((VERSION_DURESS, bytes)),
+        [VERSION_MARKER, VERSION_HISTORY, bytes @ ..] => Ok((VERSION_HISTORY, bytes)),
+        [VERSION_MARKER, VERSION_TEXTS, bytes @ ..] => Ok((VERSION_TEXTS, bytes)),
+        [VERSION_MARKER, VERSION_EXPIRY, bytes @ ..] => Ok((VERSION_EXPIRY, bytes)),
+        [VERSION_MARKER, VERSION_USAGE, bytes @ ..] => Ok((VERSION_USAGE, bytes)),
+        [VERSION_MARKER, version, ..] => bail!(VaultError::malformed(format!(
+            "Unsupported format version: {version}"
+        ))),
+        _ => Ok((VERSION_LEGACY, bytes)),
+    }
+}
+
+/// Splits a block prefixed with its `u16` length off `bytes`, returning the
+/// block and the bytes after it
+fn split_block<'a>(bytes: &'a [u8], what: &str) -> Result<(&'a [u8], &'a [u8])> {
+    let [a, b, bytes @ ..] = bytes else {
+        bail!(VaultError::malformed(format!("Missing {what} length")));
+    };
+    let len = u16::from_be_bytes([*a, *b]) as usize;
+    if bytes.len() < len {
+        bail!(VaultError::malformed(format!(
+            "Not enough bytes for {what} length"
+        )));
+    }
+    Ok(bytes.split_at(len))
+}
+
+/// Like `split_block`, for a block of `TIMESTAMP_SIZE` entries
+fn split_timestamps<'a>(bytes: &'a [u8], what: &str) -> Result<(&'a [u8], &'a [u8])> {
+    let (block, rest) = split_block(bytes, what)?;
+    if !block.len().is_multiple_of(TIMESTAMP_SIZE) {
+        bail!(VaultError::malformed(format!(
+            "The {what} are not made of whole entries"
+        )));
+    }
+    Ok((block, rest))
+}
+
+/// Fails if any bytes are left over after `what`
+fn check_trailing(trailing: usize, what: &str) -> Result<()> {
+    if trailing > 0 {
+        bail!(VaultError::malformed(format!(
+            "{trailing} trailing byte(s) after {what}"
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,168 @@
+//! Job files that describe a crack run
+//!
+//! A job file is a small TOML file, so that a run can be documented and
+//! repeated exactly:
+//!
+//! ```toml
+//! # Second half of the masters, with the pins the owner liked to use
+//! threads = 8
+//! min = 500_000_000
+//! max = 999_999_999
+//! common_file = "common.txt"
+//! output = "results.txt"
+//! ```
+//!
+//! Only the keys above are understood, each at most once and outside of any
+//! table. Every key is optional.
+
+use std::path::Path;
+
+use anyhow::{bail, Result};
+
+use crate::pins::MAX_PIN;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Job {
+    pub threads: Option<u32>,
+    /// Lowest master to scan
+    pub min: Option<u32>,
+    /// Highest master to scan
+    pub max: Option<u32>,
+    /// File of common pins, like `--common-file`
+    pub common_file: Option<String>,
+    /// File to write the results to instead of stdout
+    pub output: Option<String>,
+}
+
+/// A value as written in a job file
+enum Value {
+    Integer(u64),
+    String(String),
+}
+
+impl Job {
+    /// Parses the text of a job file, naming the line of the first problem
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut job = Self::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') {
+                bail!("Line {}: tables are not supported in job files", i + 1);
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                bail!("Line {}: expected key = value", i + 1);
+            };
+            let key = key.trim();
+            let Some(value) = parse_value(value.trim()) else {
+                bail!("Line {}: not an integer or string: {}", i + 1, value.trim());
+            };
+            let outcome = match (key, value) {
+                ("threads", Value::Integer(threads)) => {
+                    set(&mut job.threads, u32::try_from(threads).ok())
+                }
+                ("min", Value::Integer(min)) => set(&mut job.min, master(min)),
+                ("max", Value::Integer(max)) => set(&mut job.max, master(max)),
+                ("common_file", Value::String(file)) => set(&mut job.common_file, Some(file)),
+                ("output", Value::String(file)) => set(&mut job.output, Some(file)),
+                ("threads" | "min" | "max", Value::String(_)) => {
+                    bail!("Line {}: {key} must be an integer", i + 1)
+                }
+                ("common_file" | "output", Value::Integer(_)) => {
+                    bail!("Line {}: {key} must be a string", i + 1)
+                }
+                _ => bail!("Line {}: unknown key: {key}", i + 1),
+            };
+            match outcome {
+                Set::Done => (),
+                Set::Duplicate => bail!("Line {}: {key} is given twice", i + 1),
+                Set::OutOfRange => bail!("Line {}: {key} is out of range", i + 1),
+            }
+        }
+        if let (Some(min), Some(max)) = (job.min, job.max) {
+            if min > max {
+                bail!("min is above max: {min} > {max}");
+            }
+        }
+        Ok(job)
+    }
+
+    /// Makes the relative paths of a job file in `dir` relative to the
+    /// current directory instead
+    pub fn resolve_paths(&mut self, dir: &Path) {
+        for path in [&mut self.common_file, &mut self.output]
+            .into_iter()
+            .flatten()
+        {
+            if Path::new(path).is_relative() && !path.starts_with('~') {
+                *path = dir.join(&*path).to_string_lossy().into_owned();
+            }
+        }
+    }
+}
+
+/// Outcome of setting a key
+enum Set {
+    Done,
+    Duplicate,
+    OutOfRange,
+}
+
+/// Sets `field` to `value`, which is `None` if it was out of range
+fn set<T>(field: &mut Option<T>, value: Option<T>) -> Set {
+    match value {
+        _ if field.is_some() => Set::Duplicate,
+        None => Set::OutOfRange,
+        value => {
+            *field = value;
+            Set::Done
+        }
+    }
+}
+
+/// The master `value` stands for, if it is one
+fn master(value: u64) -> Option<u32> {
+    u32::try_from(value)
+        .ok()
+        .filter(|&master| master <= MAX_PIN)
+}
+
+/// Parses an integer, which may have `_` between digits, or a basic or
+/// literal string, followed by an optional comment
+fn parse_value(value: &str) -> Option<Value> {
+    let (value, rest) = match value.chars().next()? {
+        '"' => {
+            let mut string = String::new();
+            let mut chars = value.char_indices().skip(1);
+            loop {
+                match chars.next()? {
+                    (i, '"') => break (Value::String(string), &value[i + 1..]),
+                    (_, '\\') => string.push(match chars.next()?.1 {
+                        'n' => '\n',
+                        't' => '\t',
+                        c @ ('"' | '\\') => c,
+                        _ => return None,
+                    }),
+                    (_, c) => string.push(c),
+                }
+            }
+        }
+        '\'' => {
+            let (string, rest) = value[1..].split_once('\'')?;
+            (Value::String(string.to_string()), rest)
+        }
+        _ => {
+            let digits = value.split_once('#').map_or(value, |(digits, _)| digits);
+            let digits = digits.trim();
+            if digits.starts_with('_') || digits.ends_with('_') || digits.contains("__") {
+                return None;
+            }
+            let integer = digits.replace('_', "").parse().ok()?;
+            return Some(Value::Integer(integer));
+        }
+    };
+    let rest = rest.trim();
+    (rest.is_empty() || rest.starts_with('#')).then_some(value)
+}
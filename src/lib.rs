@@ -1,2 +1,7 @@
+pub mod error;
+pub mod export;
+pub mod file;
+pub mod format;
+pub mod job;
 pub mod pins;
 pub mod re;
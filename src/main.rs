@@ -1,20 +1,117 @@
-use std::{env, fs};
+use std::{
+    borrow::Cow,
+    env,
+    fs::{self, File},
+    io::{self, BufWriter, IsTerminal, Write},
+    ops::Range,
+    path::Path,
+    process::ExitCode,
+    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
-use anyhow::{bail, Result};
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use anyhow::{bail, Context, Result};
+use clipboard::Clipboard;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    style::{Color, ContentStyle},
+};
+use matcher::MatchApp;
+use qr::QrCode;
+use rand::{
+    rngs::{mock::StepRng, StdRng},
+    SeedableRng,
+};
 use ratatui::{
     prelude::*,
     symbols::border,
     widgets::{block::*, *},
 };
-use vault_crypt::{pins::Pins, re::Cracker};
+use recover::RecoverApp;
+use vault_crypt::{
+    error::VaultError,
+    export::{self, ExportFormat},
+    file,
+    job::Job,
+    pins::{self, PinKind, PinPolicy, Pins, WeakKind},
+    re::{self, spill::Spill, Cracker, Partition, ScoreMode, SusMaster},
+};
 
+pub mod clipboard;
+pub mod matcher;
+pub mod qr;
+pub mod recover;
 pub mod tui;
 
+/// Whether to print diagnostics such as timings to stderr
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+/// How many backups to keep of a vault that is overwritten
+static BACKUPS: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether saving clears the obfuscation bits instead of randomizing them
+static DETERMINISTIC: AtomicBool = AtomicBool::new(false);
+
+/// Whether commands that change a vault only print what they would change
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Whether a weak master or a vault in poor health fails a command instead
+/// of going unmentioned, see `check_health`
+static STRICT: AtomicBool = AtomicBool::new(false);
+
+/// How often the event loop wakes up to redraw when no input arrives
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// How long a revealed pin stays visible by default
+const DEFAULT_REVEAL_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a copied pin stays in the clipboard by default
+const DEFAULT_CLIPBOARD_TIMEOUT: Duration = Duration::from_secs(20);
+/// Ids left below which the header warns that the vault is filling up
+const CAPACITY_WARNING: usize = 10;
+/// Days before a pin expires from which the TUI and audit remind of it
+const EXPIRY_REMINDER: u64 = 7;
+const DAY_SECS: u64 = 24 * 60 * 60;
+/// Estimated scan time above which crack and find ask before starting
+const CONFIRM_AFTER: Duration = Duration::from_secs(10 * 60);
+/// Masters timed to estimate how long scanning all of them takes
+const SAMPLE_SIZE: u32 = 1 << 20;
+/// Results above which find prints none of them, unless they go to a file
+/// or are JSON or porcelain
+const FIND_PRINT_LIMIT: usize = 1_000;
+/// Threads crack uses when neither the arguments nor VCRY_THREADS say
+const DEFAULT_THREAD_COUNT: u32 = 4;
+
 pub struct App {
     file: String,
     bytes: Vec<u8>,
     state: AppState,
+    reveal_timeout: Duration,
+    /// System clipboard, if the session has one
+    clipboard: Option<Clipboard>,
+    clipboard_timeout: Duration,
+    /// The pin last copied and when, until it is cleared from the clipboard
+    copied: Option<(String, Instant)>,
+    /// Source of newly generated pins
+    rng: StdRng,
+    /// Rules that generated pins follow
+    pin_policy: PinPolicy,
+    /// Id of the pin to select after unlocking
+    select: Option<u8>,
+    /// Selected index and scroll offset when the vault was last locked, to
+    /// return to on unlocking
+    last_selection: Option<(Option<usize>, usize)>,
+    /// Command palette, if it is open
+    palette: Option<Palette>,
+    /// Why the last action failed, shown until the next key press
+    status: Option<String>,
+    /// Whether to lock when the terminal loses focus
+    lock_on_focus_loss: bool,
+    /// Whether the debug panel may be shown, and whether it is
+    debug: bool,
+    show_debug: bool,
+    /// Whether masters that leave the pins unencrypted may unlock
+    allow_weak_master: bool,
+    /// Whether copying records when a pin was last used, which the list shows
+    track_usage: bool,
     exit: bool,
 }
 
@@ -24,14 +121,111 @@ impl App {
             file,
             bytes,
             state: AppState::locked(),
+            reveal_timeout: DEFAULT_REVEAL_TIMEOUT,
+            clipboard: Clipboard::detect(),
+            clipboard_timeout: DEFAULT_CLIPBOARD_TIMEOUT,
+            copied: None,
+            rng: StdRng::from_entropy(),
+            pin_policy: PinPolicy::default(),
+            select: None,
+            last_selection: None,
+            palette: None,
+            status: None,
+            lock_on_focus_loss: false,
+            debug: false,
+            show_debug: false,
+            allow_weak_master: false,
+            track_usage: false,
             exit: false,
         }
     }
 
+    pub fn with_selection(mut self, id: u8) -> Self {
+        self.select = Some(id);
+        self
+    }
+
+    /// Makes pin generation reproducible
+    ///
+    /// A seeded generator is only as secret as its seed, so this is meant for
+    /// tests and demos and not for generating real pins.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    pub fn with_pin_policy(mut self, pin_policy: PinPolicy) -> Self {
+        self.pin_policy = pin_policy;
+        self
+    }
+
+    /// Starts out unlocked with `master` instead of asking for it
+    pub fn with_master(mut self, master: u32) -> Self {
+        self.unlock(master);
+        self
+    }
+
+    /// Locks the vault whenever the terminal loses focus, on terminals that
+    /// report focus changes
+    pub fn with_lock_on_focus_loss(mut self) -> Self {
+        self.lock_on_focus_loss = true;
+        self
+    }
+
+    /// Lets Shift-D show how the selected pin is encrypted, for debugging the
+    /// format
+    pub fn with_debug(mut self) -> Self {
+        self.debug = true;
+        self
+    }
+
+    /// Lets master 0 unlock, which leaves the pins unencrypted, for tests
+    pub fn with_weak_master_allowed(mut self) -> Self {
+        self.allow_weak_master = true;
+        self
+    }
+
+    /// Records when pins are copied and shows how long ago that was
+    pub fn with_usage_tracking(mut self) -> Self {
+        self.track_usage = true;
+        self
+    }
+
+    pub fn with_reveal_timeout(mut self, reveal_timeout: Duration) -> Self {
+        self.reveal_timeout = reveal_timeout;
+        self
+    }
+
+    pub fn with_clipboard_timeout(mut self, clipboard_timeout: Duration) -> Self {
+        self.clipboard_timeout = clipboard_timeout;
+        self
+    }
+
     pub fn run(&mut self, terminal: &mut tui::Tui) -> Result<()> {
-        while !self.exit {
-            terminal.draw(|frame| self.render_frame(frame))?;
-            self.handle_events()?;
+        let result = (|| {
+            while !self.exit {
+                terminal.draw(|frame| self.render_frame(frame))?;
+                self.handle_events()?;
+                if self
+                    .copied
+                    .as_ref()
+                    .is_some_and(|(_, copied)| copied.elapsed() >= self.clipboard_timeout)
+                {
+                    if let Err(err) = self.clear_clipboard() {
+                        self.status = Some(format!("Could not clear the clipboard: {err}"));
+                    }
+                }
+            }
+            Ok(())
+        })();
+        // Copied pins should not outlive the app, even if it failed
+        self.clear_clipboard().and(result)
+    }
+
+    /// Clears the clipboard if it still holds the copied pin
+    fn clear_clipboard(&mut self) -> Result<()> {
+        if let (Some(clipboard), Some((copied, _))) = (&self.clipboard, self.copied.take()) {
+            clipboard.clear_if(&copied)?;
         }
         Ok(())
     }
@@ -40,68 +234,198 @@ impl App {
         frame.render_widget(self, frame.size());
     }
 
+    /// Waits for events and handles all that arrived, so that fast input such
+    /// as a pasted pin is drawn once rather than once per key
     fn handle_events(&mut self) -> Result<()> {
-        match event::read()? {
+        if !event::poll(POLL_INTERVAL)? {
+            return Ok(());
+        }
+        self.handle_event(event::read()?)?;
+        while !self.exit && event::poll(Duration::ZERO)? {
+            self.handle_event(event::read()?)?;
+        }
+        Ok(())
+    }
+
+    fn handle_event(&mut self, event: Event) -> Result<()> {
+        match event {
             Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
                 self.handle_key_event(key_event)
             }
+            Event::FocusLost if self.lock_on_focus_loss => {
+                self.palette = None;
+                self.lock();
+                Ok(())
+            }
             _ => Ok(()),
         }
     }
 
     fn handle_key_event(&mut self, key_event: KeyEvent) -> Result<()> {
+        self.status = None;
+        if let Some(palette) = &mut self.palette {
+            match key_event.code {
+                KeyCode::Enter => {
+                    let command = palette.selected();
+                    self.palette = None;
+                    if let Some(command) = command {
+                        return self.handle_key_event(command.key);
+                    }
+                }
+                KeyCode::Esc => self.palette = None,
+                code => palette.handle_key(code),
+            }
+            return Ok(());
+        }
         let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
         match (&mut self.state, key_event.code) {
-            (AppState::Locked { input }, KeyCode::Enter) if !input.is_empty() => {
-                let master: u32 = input.parse().unwrap();
-                let pins = Pins::load(&self.bytes, master);
-                self.state = AppState::Unlocked(UnlockedState::new(pins));
+            (AppState::Unlocked(unlocked), code) if unlocked.editing.is_some() => {
+                unlocked.handle_edit_key(code);
+            }
+            (AppState::Unlocked(unlocked), code) if unlocked.confirming_compact => {
+                unlocked.confirming_compact = false;
+                if code == KeyCode::Char('y') {
+                    unlocked.modified |= !unlocked.pins.compact_ids().is_empty();
+                }
+            }
+            (AppState::Unlocked(unlocked), code) if unlocked.expiry_input.is_some() => {
+                unlocked.handle_expiry_key(code);
+            }
+            (AppState::Unlocked(_), KeyCode::Char(':')) => self.palette = Some(Palette::new()),
+            (AppState::Unlocked(_), KeyCode::Char('p')) if ctrl => {
+                self.palette = Some(Palette::new());
+            }
+            (AppState::Locked { input, .. }, KeyCode::Enter) if !input.is_empty() => {
+                let master = input.parse().unwrap();
+                self.unlock(master);
             }
-            (AppState::Locked { input }, KeyCode::Char(c @ '0'..='9')) if input.len() < 9 => {
+            (AppState::Locked { input, rejected }, KeyCode::Char(c @ '0'..='9'))
+                if input.len() < 9 =>
+            {
                 input.push(c);
+                *rejected = None;
             }
-            (AppState::Locked { input }, KeyCode::Backspace) if !input.is_empty() => {
+            (AppState::Locked { input, rejected }, KeyCode::Backspace) if !input.is_empty() => {
                 input.pop();
+                *rejected = None;
             }
             (AppState::Unlocked(unlocked), KeyCode::Char('s')) if ctrl => {
-                let bytes = unlocked.pins.save();
-                fs::write(&self.file, &bytes)?;
-                self.bytes = bytes;
+                let saved = save(&unlocked.pins)
+                    .and_then(|bytes| write_vault(&self.file, &bytes).map(|()| bytes));
+                match saved {
+                    Ok(bytes) => {
+                        self.bytes = bytes;
+                        unlocked.modified = false;
+                    }
+                    Err(err) => self.status = Some(format!("Could not save: {err}")),
+                }
             }
-            (AppState::Unlocked { .. }, KeyCode::Esc) => {
-                self.state = AppState::locked();
+            (AppState::Unlocked(unlocked), KeyCode::Char('u')) if ctrl => 'blk: {
+                let Some(i) = unlocked.state.selected() else {
+                    break 'blk;
+                };
+                match unlocked.selected_kind() {
+                    Some(PinKind::Text) => unlocked.pins.set_text(i, ""),
+                    _ => unlocked.pins.set(i, 0),
+                }
+                unlocked.modified = true;
             }
+            (AppState::Unlocked { .. }, KeyCode::Esc) => self.lock(),
             (AppState::Unlocked(unlocked), KeyCode::Char('k') | KeyCode::Up) => {
                 unlocked.previous();
             }
             (AppState::Unlocked(unlocked), KeyCode::Char('j') | KeyCode::Down) => {
                 unlocked.next();
             }
+            (AppState::Unlocked(unlocked), KeyCode::Char(' ' | 'h')) => {
+                unlocked.toggle_reveal();
+            }
+            (AppState::Unlocked(unlocked), KeyCode::Char('H')) => {
+                unlocked.toggle_reveal_all();
+            }
             (AppState::Unlocked(unlocked), KeyCode::Char('+')) => {
-                unlocked.pins.add(0);
+                unlocked.modified |= unlocked.pins.add(0);
+            }
+            (AppState::Unlocked(unlocked), KeyCode::Char('A')) => {
+                unlocked.start_rapid_entry();
+            }
+            (AppState::Unlocked(unlocked), KeyCode::Char('T')) => {
+                unlocked.add_text_and_edit();
+            }
+            (AppState::Unlocked(unlocked), KeyCode::Char('g'))
+                if unlocked.selected_kind() == Some(PinKind::Numeric) =>
+            'blk: {
+                let Some(i) = unlocked.state.selected() else {
+                    break 'blk;
+                };
+                let pin = pins::random_pin_with_policy(&mut self.rng, self.pin_policy);
+                unlocked.pins.set(i, pin);
+                unlocked.modified = true;
+            }
+            (AppState::Unlocked(unlocked), KeyCode::Char('e')) => {
+                unlocked.start_editing();
+            }
+            (AppState::Unlocked(unlocked), KeyCode::Char('C')) => {
+                unlocked.confirming_compact = true;
+            }
+            (AppState::Unlocked(_), KeyCode::Char('D')) if self.debug => {
+                self.show_debug = !self.show_debug;
             }
-            (AppState::Unlocked(unlocked), KeyCode::Char(c @ '0'..='9')) => 'blk: {
+            (AppState::Unlocked(unlocked), KeyCode::Char('x'))
+                if unlocked.state.selected().is_some() =>
+            {
+                unlocked.expiry_input = Some(String::new());
+            }
+            (AppState::Unlocked(unlocked), KeyCode::Char('c')) => 'blk: {
+                let (Some(clipboard), Some(i)) = (&self.clipboard, unlocked.state.selected())
+                else {
+                    break 'blk;
+                };
+                let pin = unlocked.pins.get(i);
+                let value = match unlocked.pins.text(pin.id) {
+                    Some(text) => text.to_string(),
+                    None if pin.pin == 0 => break 'blk,
+                    None => pin.pin.to_string(),
+                };
+                if let Err(err) = clipboard.copy(&value) {
+                    self.status = Some(format!("Could not copy: {err}"));
+                    break 'blk;
+                }
+                self.copied = Some((value, Instant::now()));
+                if self.track_usage {
+                    unlocked.pins.mark_used(i, unix_now());
+                    unlocked.modified = true;
+                }
+            }
+            (AppState::Unlocked(unlocked), KeyCode::Char(c @ '0'..='9'))
+                if unlocked.selected_kind() == Some(PinKind::Numeric) =>
+            'blk: {
                 let Some(i) = unlocked.state.selected() else {
                     break 'blk;
                 };
                 let pin = unlocked.pins.get(i).pin;
                 if pin < 100_000_000 {
                     let digit = c as u32 - '0' as u32;
-                    unlocked.pins.set(i, pin * 10 + digit);
+                    unlocked.pins.amend(i, pin * 10 + digit);
+                    unlocked.modified = true;
                 }
             }
-            (AppState::Unlocked(unlocked), KeyCode::Backspace) => 'blk: {
+            (AppState::Unlocked(unlocked), KeyCode::Backspace)
+                if unlocked.selected_kind() == Some(PinKind::Numeric) =>
+            'blk: {
                 let Some(i) = unlocked.state.selected() else {
                     break 'blk;
                 };
                 let pin = unlocked.pins.get(i).pin;
-                unlocked.pins.set(i, pin / 10);
+                unlocked.pins.amend(i, pin / 10);
+                unlocked.modified = true;
             }
             (AppState::Unlocked(unlocked), KeyCode::Delete) => 'blk: {
                 let Some(i) = unlocked.state.selected() else {
                     break 'blk;
                 };
                 unlocked.pins.remove(i);
+                unlocked.modified = true;
                 if unlocked.pins.is_empty() {
                     unlocked.unselect();
                 } else if unlocked.pins.len() <= i {
@@ -114,7 +438,34 @@ impl App {
         Ok(())
     }
 
+    /// Unlocks the vault with `master`, or stays locked saying why it is
+    /// rejected
+    fn unlock(&mut self, master: u32) {
+        if let Err(err) = pins::check_master(master, self.allow_weak_master) {
+            self.state = AppState::Locked {
+                input: String::new(),
+                rejected: Some(err.to_string()),
+            };
+            return;
+        }
+        let pins = timed("load", || Pins::load(&self.bytes, master));
+        let mut unlocked = UnlockedState::new(pins, self.select);
+        if let Some((selected, offset)) = self.last_selection {
+            unlocked.restore_selection(selected, offset);
+        }
+        self.state = AppState::Unlocked(Box::new(unlocked));
+    }
+
+    /// Locks the vault, remembering the selection
+    fn lock(&mut self) {
+        if let AppState::Unlocked(unlocked) = &self.state {
+            self.last_selection = Some((unlocked.state.selected(), unlocked.state.offset()));
+        }
+        self.state = AppState::locked();
+    }
+
     fn exit(&mut self) {
+        self.last_selection = None;
         self.exit = true;
     }
 }
@@ -129,19 +480,88 @@ impl Widget for &mut App {
                     instructions.push(" Unlock".into());
                     instructions.push("<Enter>".blue().bold());
                 }
+                AppState::Unlocked(_) if self.palette.is_some() => {
+                    instructions.push(" Run".into());
+                    instructions.push("<Enter>".blue().bold());
+                    instructions.push(" Close".into());
+                    instructions.push("<Esc>".blue().bold());
+                }
+                AppState::Unlocked(unlocked) if unlocked.confirming_compact => {
+                    instructions.push(" Renumber all pins from 0, changing their ids?".into());
+                    instructions.push(" Yes".into());
+                    instructions.push("<Y>".blue().bold());
+                    instructions.push(" No".into());
+                    instructions.push("<any key>".blue().bold());
+                }
+                AppState::Unlocked(unlocked) if unlocked.expiry_input.is_some() => {
+                    let input = unlocked.expiry_input.as_deref().unwrap_or_default();
+                    instructions.push(format!(" Expires in days: {input}_").into());
+                    instructions.push(" Set, or clear if empty".into());
+                    instructions.push("<Enter>".blue().bold());
+                    instructions.push(" Cancel".into());
+                    instructions.push("<Esc>".blue().bold());
+                }
+                AppState::Unlocked(unlocked) if unlocked.rapid_entry => {
+                    instructions.push(" Next pin".into());
+                    instructions.push("<Enter>".blue().bold());
+                    instructions.push(" Stop".into());
+                    instructions.push("<Esc>".blue().bold());
+                }
+                AppState::Unlocked(unlocked) if unlocked.editing.is_some() => {
+                    instructions.push(" Commit".into());
+                    instructions.push("<Enter>".blue().bold());
+                    instructions.push(" Cancel".into());
+                    instructions.push("<Esc>".blue().bold());
+                }
                 AppState::Unlocked { .. } => {
+                    instructions.push(" Commands".into());
+                    instructions.push("<:>".blue().bold());
                     instructions.push(" Save".into());
                     instructions.push("<Ctrl-S>".blue().bold());
                     instructions.push(" New pin".into());
                     instructions.push("<+>".blue().bold());
+                    instructions.push(" Rapid entry".into());
+                    instructions.push("<Shift-A>".blue().bold());
+                    instructions.push(" New text".into());
+                    instructions.push("<Shift-T>".blue().bold());
+                    instructions.push(" Generate".into());
+                    instructions.push("<G>".blue().bold());
+                    instructions.push(" Edit pin".into());
+                    instructions.push("<E>".blue().bold());
+                    instructions.push(" Copy".into());
+                    instructions.push("<C>".blue().bold());
+                    instructions.push(" Compact ids".into());
+                    instructions.push("<Shift-C>".blue().bold());
+                    instructions.push(" Expiry".into());
+                    instructions.push("<X>".blue().bold());
+                    instructions.push(" Clear pin".into());
+                    instructions.push("<Ctrl-U>".blue().bold());
                     instructions.push(" Remove pin".into());
                     instructions.push("<Del>".blue().bold());
+                    instructions.push(" Reveal".into());
+                    instructions.push("<Space>".blue().bold());
+                    instructions.push(" Reveal all".into());
+                    instructions.push("<Shift-H>".blue().bold());
+                    if self.debug {
+                        instructions.push(" Debug".into());
+                        instructions.push("<Shift-D>".blue().bold());
+                    }
                     instructions.push(" Lock".into());
                     instructions.push("<Esc>".blue().bold());
                 }
             }
-            instructions.push(" Quit".into());
-            instructions.push("<Q>".blue().bold());
+            if self.palette.is_none()
+                && !matches!(
+                    &self.state,
+                    AppState::Unlocked(unlocked)
+                        if unlocked.editing.is_some()
+                            || unlocked.confirming_compact
+                            || unlocked.expiry_input.is_some()
+                )
+            {
+                instructions.push(" Quit".into());
+                instructions.push("<Q>".blue().bold());
+            }
             instructions
         }));
         let block = Block::default()
@@ -155,41 +575,157 @@ impl Widget for &mut App {
             .border_set(border::THICK);
 
         match &mut self.state {
-            AppState::Locked { input } => Paragraph::new(format!("Master Pin: {input:_<9}"))
-                .centered()
-                .block(block)
-                .render(area, buf),
+            AppState::Locked { input, rejected } => {
+                let mut text = Text::from(format!("Master Pin: {input:_<9}"));
+                if let Some(rejected) = rejected {
+                    text.push_line(rejected.clone().red());
+                }
+                if let Some(status) = &self.status {
+                    text.push_line(status.clone().red());
+                }
+                Paragraph::new(text)
+                    .centered()
+                    .block(block)
+                    .render(area, buf);
+            }
             AppState::Unlocked(unlocked) => {
+                for revealed in [&mut unlocked.revealed, &mut unlocked.revealed_all] {
+                    if revealed.is_some_and(|revealed| revealed.elapsed() >= self.reveal_timeout) {
+                        *revealed = None;
+                    }
+                }
+                let inner = block.inner(area);
+                block.render(area, buf);
+                let mut header = unlocked.header();
+                if let Some(status) = &self.status {
+                    header.push_line(status.clone().red());
+                }
+                let [header_area, list_area, debug_area] = Layout::vertical([
+                    Constraint::Length(header.height() as u16),
+                    Constraint::Min(0),
+                    Constraint::Length(if self.show_debug { 4 } else { 0 }),
+                ])
+                .areas(inner);
+                if self.show_debug {
+                    Paragraph::new(unlocked.debug_lines(&self.bytes))
+                        .block(Block::default().title("Debug").borders(Borders::TOP))
+                        .render(debug_area, buf);
+                }
+                Paragraph::new(header).render(header_area, buf);
+                if unlocked.pins.is_empty() {
+                    let [_, hint_area, _] = Layout::vertical([
+                        Constraint::Fill(1),
+                        Constraint::Length(1),
+                        Constraint::Fill(1),
+                    ])
+                    .areas(list_area);
+                    Paragraph::new("No pins yet — press + to add one".dark_gray())
+                        .centered()
+                        .render(hint_area, buf);
+                }
+
+                let selected = unlocked.state.selected();
+                let revealed = unlocked.revealed.and(selected);
+                let revealed_all = unlocked.revealed_all.is_some();
+                let editing = unlocked.editing.as_ref();
+                let now = unix_now();
+                let track_usage = self.track_usage;
                 StatefulWidget::render(
-                    List::new(
-                        unlocked
-                            .pins
-                            .iter()
-                            .map(|pin| format!("Pin {:2}: {:-<9}", pin.id, pin.pin)),
-                    )
+                    List::new(unlocked.pins.iter().enumerate().map(|(i, pin)| {
+                        let mut line = match editing {
+                            Some(input) if selected == Some(i) => {
+                                let mut line = input.line();
+                                let label = match unlocked.pins.kind(pin.id) {
+                                    PinKind::Numeric => "Pin",
+                                    PinKind::Text => "Txt",
+                                };
+                                line.spans
+                                    .insert(0, format!("{label} {:2}: ", pin.id).into());
+                                line
+                            }
+                            _ if unlocked.pins.kind(pin.id) == PinKind::Text => {
+                                let text = unlocked.pins.text(pin.id).unwrap_or_default();
+                                if revealed_all || revealed == Some(i) {
+                                    format!("Txt {:2}: {text}", pin.id).into()
+                                } else {
+                                    format!("Txt {:2}: *********", pin.id).into()
+                                }
+                            }
+                            // Empty pins are not written when saving
+                            _ if pin.pin == 0 => Line::from(vec![
+                                format!("Pin {:2}: ", pin.id).into(),
+                                "(empty)".dark_gray().italic(),
+                            ]),
+                            _ if revealed_all || revealed == Some(i) => {
+                                let mut line =
+                                    Line::from(format!("Pin {:2}: {:-<9}", pin.id, pin.pin));
+                                let kind = pins::classify(pin.pin);
+                                if selected == Some(i) && kind != WeakKind::Ok {
+                                    line.spans.push(format!("  weak: {kind}").yellow());
+                                }
+                                let history = unlocked.pins.history(pin.id);
+                                if selected == Some(i) && !history.is_empty() {
+                                    let history: Vec<_> =
+                                        history.iter().rev().map(u32::to_string).collect();
+                                    line.spans
+                                        .push(format!("  was {}", history.join(", ")).dark_gray());
+                                }
+                                line
+                            }
+                            _ => format!("Pin {:2}: *********", pin.id).into(),
+                        };
+                        if let Some(expires) = unlocked.pins.expires(pin.id) {
+                            match expiry_days(expires, now) {
+                                None => line.spans.push("  expired".red()),
+                                Some(days) if days <= EXPIRY_REMINDER => {
+                                    line.spans
+                                        .push(format!("  expires in {days} day(s)").yellow());
+                                }
+                                Some(_) => (),
+                            }
+                        }
+                        if track_usage {
+                            let used = match unlocked.pins.last_used(pin.id) {
+                                None => "  never used".to_string(),
+                                Some(used) => match now.saturating_sub(used) / DAY_SECS {
+                                    0 => "  used today".to_string(),
+                                    days => format!("  used {days} day(s) ago"),
+                                },
+                            };
+                            line.spans.push(used.dark_gray());
+                        }
+                        line
+                    }))
                     .highlight_style(Style::default().green())
                     .highlight_symbol(">>")
                     .repeat_highlight_symbol(true)
-                    .direction(ListDirection::TopToBottom)
-                    .block(block),
-                    area,
+                    .direction(ListDirection::TopToBottom),
+                    list_area,
                     buf,
                     &mut unlocked.state,
                 );
+                if let Some(palette) = &mut self.palette {
+                    palette.render(inner, buf);
+                }
             }
         }
     }
 }
 
 pub enum AppState {
-    Locked { input: String },
-    Unlocked(UnlockedState),
+    Locked {
+        input: String,
+        /// Why the last master entered was rejected, if it was
+        rejected: Option<String>,
+    },
+    Unlocked(Box<UnlockedState>),
 }
 
 impl AppState {
     pub fn locked() -> Self {
         Self::Locked {
             input: String::new(),
+            rejected: None,
         }
     }
 }
@@ -198,14 +734,237 @@ pub struct UnlockedState {
     pins: Pins,
     state: ListState,
     last_selected: Option<usize>,
+    /// When the selected pin was revealed, if it currently is
+    revealed: Option<Instant>,
+    /// When all pins were revealed, if they currently are
+    revealed_all: Option<Instant>,
+    /// Editor for the selected pin, if it is being edited
+    editing: Option<PinInput>,
+    /// Whether the pins changed since they were last saved
+    modified: bool,
+    /// Whether committing an edit adds and edits the next pin
+    rapid_entry: bool,
+    /// Whether compacting the ids waits for confirmation
+    confirming_compact: bool,
+    /// Days until the selected pin expires, if they are being entered
+    expiry_input: Option<String>,
 }
 
 impl UnlockedState {
-    pub fn new(pins: Pins) -> Self {
+    /// Creates the state for freshly unlocked pins, selecting the pin with id
+    /// `select` if there is one
+    pub fn new(pins: Pins, select: Option<u8>) -> Self {
+        let selected = select.and_then(|id| pins.iter_ids().position(|pin_id| pin_id == id));
         Self {
             pins,
-            state: ListState::default(),
+            state: ListState::default().with_selected(selected),
             last_selected: None,
+            revealed: None,
+            revealed_all: None,
+            editing: None,
+            modified: false,
+            rapid_entry: false,
+            confirming_compact: false,
+            expiry_input: None,
+        }
+    }
+
+    /// Selects the pin at `selected` and scrolls to `offset`, as far as there
+    /// are pins
+    fn restore_selection(&mut self, selected: Option<usize>, offset: usize) {
+        let last = self.pins.len().checked_sub(1);
+        self.state.select(selected.and_then(|i| Some(i.min(last?))));
+        *self.state.offset_mut() = offset.min(last.unwrap_or(0));
+    }
+
+    /// Summary of the vault's health shown above the pins
+    fn header(&self) -> Text<'static> {
+        let mut header = vec![
+            format!("{} pins", self.pins.len()).into(),
+            " · ".dark_gray(),
+            format!("{} weak", self.pins.count_weak()).into(),
+            " · ".dark_gray(),
+            format!("{} duplicates", self.pins.count_duplicates()).into(),
+            " · ".dark_gray(),
+        ];
+        let remaining = self.pins.remaining();
+        header.push(match remaining {
+            0 if self.pins.len() < 100 => "full, compact ids to add more".red().bold(),
+            0 => "full".red().bold(),
+            1..=CAPACITY_WARNING => format!("{remaining} ids left").yellow(),
+            _ => format!("{remaining} ids left").into(),
+        });
+        if self.modified {
+            header.push(" · ".dark_gray());
+            header.push("[unsaved]".yellow());
+        }
+        let mut header = Text::from(Line::from(header));
+        if !self.pins.metadata().is_empty() {
+            header.push_line(self.pins.metadata().to_string().italic());
+        }
+        header
+    }
+
+    pub fn start_editing(&mut self) {
+        let Some(i) = self.state.selected() else {
+            return;
+        };
+        let pin = self.pins.get(i);
+        self.editing = Some(match self.pins.text(pin.id) {
+            Some(text) => PinInput::text(text),
+            None => PinInput::new(pin.pin),
+        });
+    }
+
+    /// How the selected pin is encrypted under the current master, and how
+    /// it is stored in `bytes`, the vault as last saved
+    fn debug_lines(&self, bytes: &[u8]) -> Text<'static> {
+        let Some(i) = self.state.selected() else {
+            return Text::from("No pin selected".dark_gray());
+        };
+        let hex = |value: u32| {
+            let bytes: Vec<_> = value
+                .to_be_bytes()
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect();
+            bytes.join(" ")
+        };
+        let master = self.pins.master();
+        let pin = self.pins.get(i);
+        let keystream = pins::n_shift(master, pin.id + 1);
+        let mut text = Text::from(format!(
+            "Id {} · keystream n_shift(master, {}) = {}",
+            pin.id,
+            pin.id + 1,
+            hex(keystream)
+        ));
+        if self.pins.kind(pin.id) == PinKind::Text {
+            text.push_line("Text secret, stored in the text block");
+            return text;
+        }
+        match pins::encrypt(master, pin.id, pin.pin, &mut StepRng::new(0, 0)) {
+            Ok(encrypted) => text.push_line(format!(
+                "Encrypted {} (obfuscation bits cleared)",
+                hex(encrypted)
+            )),
+            Err(err) => text.push_line(format!("Cannot be encrypted: {err}").red()),
+        }
+        text.push_line(match pins::stored_pin(bytes, master, pin.id) {
+            Some(stored) if pins::decrypt(master, pin.id, stored) == pin.pin => {
+                Line::from(format!("Stored {}, matching the pin", hex(stored)))
+            }
+            Some(stored) => Line::from(
+                format!("Stored {}, differing from the unsaved pin", hex(stored)).yellow(),
+            ),
+            None => Line::from("Not stored, the vault is unsaved".dark_gray()),
+        });
+        text
+    }
+
+    /// Kind of the selected pin, if one is selected
+    fn selected_kind(&self) -> Option<PinKind> {
+        let i = self.state.selected()?;
+        Some(self.pins.kind(self.pins.get(i).id))
+    }
+
+    /// Adds an empty text secret and starts editing it
+    pub fn add_text_and_edit(&mut self) {
+        if !self.pins.add_text("") {
+            return;
+        }
+        self.modified = true;
+        self.revealed = None;
+        self.state.select(Some(self.pins.len() - 1));
+        self.start_editing();
+    }
+
+    /// Adds empty pins one after the other, editing each, until the edit is
+    /// cancelled
+    pub fn start_rapid_entry(&mut self) {
+        self.rapid_entry = self.add_and_edit();
+    }
+
+    /// Adds an empty pin and starts editing it, returning whether there was
+    /// room for it
+    fn add_and_edit(&mut self) -> bool {
+        if !self.pins.add(0) {
+            return false;
+        }
+        self.modified = true;
+        self.revealed = None;
+        self.state.select(Some(self.pins.len() - 1));
+        self.start_editing();
+        true
+    }
+
+    pub fn handle_edit_key(&mut self, code: KeyCode) {
+        let Some(input) = &mut self.editing else {
+            return;
+        };
+        match code {
+            KeyCode::Enter => {
+                if let Some(i) = self.state.selected() {
+                    if input.text {
+                        self.pins.set_text(i, input.digits.clone());
+                    } else {
+                        self.pins.set(i, input.value());
+                    }
+                    self.modified = true;
+                }
+                self.editing = None;
+                if self.rapid_entry {
+                    self.rapid_entry = self.add_and_edit();
+                }
+            }
+            KeyCode::Esc => {
+                self.editing = None;
+                self.rapid_entry = false;
+            }
+            code => input.handle_key(code),
+        }
+    }
+
+    /// Sets the selected pin to expire in the entered number of days, or
+    /// clears its expiry if none were entered
+    pub fn handle_expiry_key(&mut self, code: KeyCode) {
+        let Some(input) = &mut self.expiry_input else {
+            return;
+        };
+        match code {
+            KeyCode::Char(c @ '0'..='9') if input.len() < 5 => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Enter => {
+                if let Some(i) = self.state.selected() {
+                    let expires = input
+                        .parse::<u64>()
+                        .ok()
+                        .map(|days| unix_now() + days * DAY_SECS);
+                    self.pins.set_expiry(i, expires);
+                    self.modified = true;
+                }
+                self.expiry_input = None;
+            }
+            KeyCode::Esc => self.expiry_input = None,
+            _ => (),
+        }
+    }
+
+    pub fn toggle_reveal(&mut self) {
+        if self.revealed.is_some() || self.state.selected().is_none() {
+            self.revealed = None;
+        } else {
+            self.revealed = Some(Instant::now());
+        }
+    }
+
+    pub fn toggle_reveal_all(&mut self) {
+        if self.revealed_all.is_some() {
+            self.revealed_all = None;
+        } else {
+            self.revealed_all = Some(Instant::now());
         }
     }
 
@@ -223,6 +982,7 @@ impl UnlockedState {
             }
             None => self.last_selected.unwrap_or(0),
         };
+        self.revealed = None;
         self.state.select(Some(i));
     }
 
@@ -240,84 +1000,1875 @@ impl UnlockedState {
             }
             None => self.last_selected.unwrap_or(0),
         };
+        self.revealed = None;
         self.state.select(Some(i));
     }
 
     pub fn unselect(&mut self) {
         let offset = self.state.offset();
         self.last_selected = self.state.selected();
+        self.revealed = None;
         self.state.select(None);
         *self.state.offset_mut() = offset;
     }
 }
 
-fn main() -> Result<()> {
-    let args: Box<[_]> = env::args().skip(1).collect();
-    let args: Box<[_]> = args.iter().map(String::as_str).collect();
-    match args.as_ref() {
-        ["crack" | "c", file] => crack(file, 4),
-        ["crack" | "c", file, thread_count] => {
-            let thread_count: u32 = thread_count.parse()?;
-            crack(file, thread_count)
+/// An action offered by the command palette
+pub struct Command {
+    name: &'static str,
+    /// Key binding that performs the action, which the palette replays
+    key: KeyEvent,
+    hint: &'static str,
+}
+
+impl Command {
+    const fn new(name: &'static str, code: KeyCode, hint: &'static str) -> Self {
+        Self::with_modifiers(name, code, KeyModifiers::NONE, hint)
+    }
+
+    const fn with_modifiers(
+        name: &'static str,
+        code: KeyCode,
+        modifiers: KeyModifiers,
+        hint: &'static str,
+    ) -> Self {
+        Self {
+            name,
+            key: KeyEvent::new(code, modifiers),
+            hint,
         }
-        ["find" | "f", file, thread_count, known_pins @ ..] => {
-            let thread_count: u32 = thread_count.parse()?;
-            let mut pins = Vec::new();
-            for pin in known_pins {
-                pins.push(pin.parse()?);
+    }
+}
+
+static COMMANDS: [Command; 15] = [
+    Command::with_modifiers(
+        "Save",
+        KeyCode::Char('s'),
+        KeyModifiers::CONTROL,
+        "<Ctrl-S>",
+    ),
+    Command::new("New pin", KeyCode::Char('+'), "<+>"),
+    Command::new("Rapid entry", KeyCode::Char('A'), "<Shift-A>"),
+    Command::new("New text secret", KeyCode::Char('T'), "<Shift-T>"),
+    Command::new("Generate pin", KeyCode::Char('g'), "<G>"),
+    Command::new("Edit pin", KeyCode::Char('e'), "<E>"),
+    Command::new("Copy pin", KeyCode::Char('c'), "<C>"),
+    Command::new("Compact ids", KeyCode::Char('C'), "<Shift-C>"),
+    Command::new("Set expiry", KeyCode::Char('x'), "<X>"),
+    Command::with_modifiers(
+        "Clear pin",
+        KeyCode::Char('u'),
+        KeyModifiers::CONTROL,
+        "<Ctrl-U>",
+    ),
+    Command::new("Remove pin", KeyCode::Delete, "<Del>"),
+    Command::new("Reveal pin", KeyCode::Char(' '), "<Space>"),
+    Command::new("Reveal all pins", KeyCode::Char('H'), "<Shift-H>"),
+    Command::new("Lock", KeyCode::Esc, "<Esc>"),
+    Command::new("Quit", KeyCode::Char('q'), "<Q>"),
+];
+
+/// Filterable list of `COMMANDS`
+pub struct Palette {
+    filter: String,
+    state: ListState,
+}
+
+impl Palette {
+    fn new() -> Self {
+        Self {
+            filter: String::new(),
+            state: ListState::default().with_selected(Some(0)),
+        }
+    }
+
+    /// Commands whose name contains the filter, ignoring case
+    fn matches(&self) -> Vec<&'static Command> {
+        let filter = self.filter.to_lowercase();
+        COMMANDS
+            .iter()
+            .filter(|command| command.name.to_lowercase().contains(&filter))
+            .collect()
+    }
+
+    fn selected(&self) -> Option<&'static Command> {
+        self.state
+            .selected()
+            .and_then(|i| self.matches().get(i).copied())
+    }
+
+    fn handle_key(&mut self, code: KeyCode) {
+        let count = self.matches().len();
+        match code {
+            KeyCode::Up => {
+                let i = self.state.selected().unwrap_or(0);
+                self.state.select(Some(i.saturating_sub(1)));
+            }
+            KeyCode::Down if count > 0 => {
+                let i = self.state.selected().unwrap_or(0);
+                self.state.select(Some((i + 1).min(count - 1)));
+            }
+            KeyCode::Char(c) => {
+                self.filter.push(c);
+                self.refilter();
+            }
+            KeyCode::Backspace => {
+                self.filter.pop();
+                self.refilter();
             }
-            find(file, thread_count, &pins)
+            _ => (),
         }
-        ["open" | "o", file] | [file] => {
-            let path = std::path::Path::new(&file);
-            let bytes = if path.exists() {
-                fs::read(path)?
-            } else {
-                vec![0x00]
-            };
-            Pins::verify(&bytes)?;
-            let mut terminal = tui::init()?;
-            let app_result = App::new(file.to_string(), bytes).run(&mut terminal);
-            tui::restore()?;
-            app_result
+    }
+
+    /// Selects the first command left after the filter changed
+    fn refilter(&mut self) {
+        let selected = (!self.matches().is_empty()).then_some(0);
+        self.state.select(selected);
+    }
+
+    /// Renders the palette as a popup centered in `area`
+    fn render(&mut self, area: Rect, buf: &mut Buffer) {
+        let width = 40.min(area.width);
+        let height = (COMMANDS.len() as u16 + 3).min(area.height);
+        let popup = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height,
+        };
+        Clear.render(popup, buf);
+        let block = Block::default().title(" Commands ").borders(Borders::ALL);
+        let inner = block.inner(popup);
+        block.render(popup, buf);
+        let [filter_area, list_area] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(inner);
+        Paragraph::new(Line::from(vec![
+            ": ".dark_gray(),
+            self.filter.clone().into(),
+        ]))
+        .render(filter_area, buf);
+        let name_width = list_area.width.saturating_sub(12) as usize;
+        StatefulWidget::render(
+            List::new(self.matches().into_iter().map(|command| {
+                Line::from(vec![
+                    format!("{:name_width$}", command.name).into(),
+                    command.hint.blue().bold(),
+                ])
+            }))
+            .highlight_style(Style::default().green())
+            .highlight_symbol(">>"),
+            list_area,
+            buf,
+            &mut self.state,
+        );
+    }
+}
+
+/// Single-line editor for a pin value, or for a text secret
+pub struct PinInput {
+    /// The digits, or the text of a text secret
+    digits: String,
+    /// Cursor position in characters
+    cursor: usize,
+    /// Whether any characters are accepted rather than only digits
+    text: bool,
+}
+
+impl PinInput {
+    pub fn new(pin: u32) -> Self {
+        let digits = if pin == 0 {
+            String::new()
+        } else {
+            pin.to_string()
+        };
+        Self {
+            cursor: digits.len(),
+            digits,
+            text: false,
         }
-        _ => bail!(
-            "    Usage
-vcry crack <file>
-vcry crack <file> <thread count>
-vcry find <file> <thread count> <known pins...>
-vcry open <file>
-vcry <file>"
-        ),
+    }
+
+    /// Creates an editor for the text secret `text`
+    pub fn text(text: &str) -> Self {
+        Self {
+            cursor: text.chars().count(),
+            digits: text.to_string(),
+            text: true,
+        }
+    }
+
+    pub fn value(&self) -> u32 {
+        self.digits.parse().unwrap_or(0)
+    }
+
+    pub fn handle_key(&mut self, code: KeyCode) {
+        let len = self.digits.chars().count();
+        match code {
+            KeyCode::Char(c @ '0'..='9') if !self.text && len < 9 => {
+                self.digits.insert(self.byte_offset(), c);
+                self.cursor += 1;
+            }
+            KeyCode::Char(c)
+                if self.text && self.digits.len() + c.len_utf8() <= pins::MAX_TEXT_LEN =>
+            {
+                self.digits.insert(self.byte_offset(), c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.digits.remove(self.byte_offset());
+            }
+            KeyCode::Delete if self.cursor < len => {
+                self.digits.remove(self.byte_offset());
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(len),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = len,
+            _ => (),
+        }
+    }
+
+    /// Byte offset of the cursor in `digits`
+    fn byte_offset(&self) -> usize {
+        self.digits
+            .char_indices()
+            .nth(self.cursor)
+            .map_or(self.digits.len(), |(i, _)| i)
+    }
+
+    /// Renders the digits with the cursor shown as a reversed cell
+    fn line(&self) -> Line<'static> {
+        let (before, after) = self.digits.split_at(self.byte_offset());
+        let mut after = after.chars();
+        let cursor = after.next().unwrap_or(' ');
+        Line::from(vec![
+            before.to_string().into(),
+            cursor.to_string().reversed(),
+            after.as_str().to_string().into(),
+        ])
     }
 }
 
-fn crack(file: &str, thread_count: u32) -> Result<()> {
-    let bytes = fs::read(file)?;
-    Pins::verify(&bytes)?;
-    let cracker = Cracker::load(&bytes);
-    eprintln!(">> Cracking vault with {thread_count} thread(s).");
-    let mut sus_pins = cracker.bruteforce_threaded(thread_count);
-    eprintln!(">> Done. Found {} suspicious master pins.", sus_pins.len());
-    sus_pins.sort_by_key(|sus| u32::MAX - sus.score);
-    for sus in &sus_pins {
-        println!("{sus}");
+/// Exit codes for failures that scripts may want to handle differently
+const EXIT_FAILURE: u8 = 1;
+const EXIT_WRONG_MASTER: u8 = 2;
+const EXIT_MALFORMED: u8 = 3;
+const EXIT_NOT_FOUND: u8 = 4;
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err:?}");
+            ExitCode::from(exit_code(&err))
+        }
     }
-    Ok(())
 }
 
-fn find(file: &str, thread_count: u32, known_pins: &[u32]) -> Result<()> {
-    let bytes = fs::read(file)?;
-    Pins::verify(&bytes)?;
-    let cracker = Cracker::load(&bytes);
-    eprintln!(">> Finding pins in vault with {thread_count} thread(s).");
-    let mut sus_pins = cracker.find_threaded(thread_count, known_pins);
-    eprintln!(">> Done. Found {} suspicious master pins.", sus_pins.len());
-    sus_pins.sort_by_key(|sus| u32::MAX - sus.score);
-    if sus_pins.len() <= 1_000 {
-        for sus in &sus_pins {
-            println!("{sus}");
+fn exit_code(err: &anyhow::Error) -> u8 {
+    if let Some(err) = err.downcast_ref::<VaultError>() {
+        return match err {
+            VaultError::WrongMaster => EXIT_WRONG_MASTER,
+            VaultError::Malformed(_) => EXIT_MALFORMED,
+        };
+    }
+    match err.downcast_ref::<io::Error>() {
+        Some(err) if err.kind() == io::ErrorKind::NotFound => EXIT_NOT_FOUND,
+        _ => EXIT_FAILURE,
+    }
+}
+
+fn run() -> Result<()> {
+    let args: Box<[_]> = env::args().skip(1).collect();
+    let mut args: Vec<_> = args.iter().map(String::as_str).collect();
+    if take_flag(&mut args, "--verbose") | take_flag(&mut args, "-v") {
+        VERBOSE.store(true, Ordering::Relaxed);
+    }
+    let backups = match take_option(&mut args, "--backups")? {
+        Some(backups) => Some(backups.to_string()),
+        None => env::var("VCRY_BACKUPS").ok(),
+    };
+    if let Some(backups) = backups {
+        BACKUPS.store(backups.parse()?, Ordering::Relaxed);
+    }
+    if take_flag(&mut args, "--deterministic") {
+        DETERMINISTIC.store(true, Ordering::Relaxed);
+    }
+    if take_flag(&mut args, "--dry-run") {
+        DRY_RUN.store(true, Ordering::Relaxed);
+    }
+    if take_flag(&mut args, "--strict") {
+        STRICT.store(true, Ordering::Relaxed);
+    }
+    let reveal_timeout = match take_option(&mut args, "--reveal-secs")? {
+        Some(secs) => Duration::from_secs(secs.parse()?),
+        None => DEFAULT_REVEAL_TIMEOUT,
+    };
+    let clipboard_timeout = match take_option(&mut args, "--clipboard-secs")? {
+        Some(secs) => Duration::from_secs(secs.parse()?),
+        None => DEFAULT_CLIPBOARD_TIMEOUT,
+    };
+    let select = match take_option(&mut args, "--select")? {
+        Some(id) => Some(id.parse()?),
+        None => None,
+    };
+    let seed = match take_option(&mut args, "--seed")? {
+        Some(seed) => Some(seed.parse()?),
+        None => match env::var("VCRY_SEED") {
+            Ok(seed) => Some(seed.parse()?),
+            Err(_) => None,
+        },
+    };
+    let allow_weak = take_flag(&mut args, "--allow-weak");
+    let allow_weak_master = take_flag(&mut args, "--allow-weak-master");
+    if allow_weak_master && STRICT.load(Ordering::Relaxed) {
+        bail!("--strict and --allow-weak-master cannot be combined");
+    }
+    let pin_policy = match take_option(&mut args, "--pin-length")? {
+        Some(length) => PinPolicy::new(length.parse()?, !allow_weak)?,
+        None => PinPolicy::new(9, !allow_weak)?,
+    };
+    let score_mode = if take_flag(&mut args, "--validity-only") {
+        ScoreMode::ValidityOnly
+    } else {
+        ScoreMode::CommonPins
+    };
+    let spill = take_flag(&mut args, "--spill");
+    let lock_on_focus_loss = take_flag(&mut args, "--lock-on-focus-loss")
+        || env::var_os("VCRY_LOCK_ON_FOCUS_LOSS").is_some_and(|v| !v.is_empty());
+    let yes = take_flag(&mut args, "--yes");
+    let debug = take_flag(&mut args, "--debug");
+    let track_usage = take_flag(&mut args, "--track-usage");
+    let json = take_flag(&mut args, "--json");
+    let porcelain = take_flag(&mut args, "--porcelain");
+    let output = match (json, porcelain) {
+        (true, true) => bail!("--json and --porcelain cannot be combined"),
+        (true, false) => Output::Json,
+        (false, true) => Output::Porcelain,
+        (false, false) => Output::Human,
+    };
+    let free = take_flag(&mut args, "--free");
+    let group = take_flag(&mut args, "--group");
+    let ids = match take_option(&mut args, "--ids")? {
+        Some(ids) => Some(
+            ids.split(',')
+                .map(|id| id.trim().parse())
+                .collect::<Result<Vec<u8>, _>>()?,
+        ),
+        None => None,
+    };
+    let output_ids = match take_option(&mut args, "--output-ids")? {
+        Some(ids) => ids
+            .split(',')
+            .map(|id| id.trim().parse())
+            .collect::<Result<Vec<u8>, _>>()?,
+        None => Vec::new(),
+    };
+    let rate_limit = match take_option(&mut args, "--limit-rate")? {
+        Some(percent) => match percent.parse() {
+            Ok(percent @ 1..=100) => Some(percent),
+            _ => bail!("--limit-rate must be a percentage from 1 to 100"),
+        },
+        None => None,
+    };
+    let max_results = match take_option(&mut args, "--max-results")? {
+        Some(count) => match count.parse() {
+            Ok(count @ 1..) => Some(count),
+            _ => bail!("--max-results must be a positive number"),
+        },
+        None => None,
+    };
+    let job = match take_option(&mut args, "--job")? {
+        Some(job_file) => {
+            let job_file = file::expand_home(job_file);
+            let mut job = Job::parse(&read_text("job file", &job_file)?)
+                .with_context(|| format!("Could not parse job file '{job_file}'"))?;
+            if let Some(dir) = Path::new(&*job_file).parent() {
+                job.resolve_paths(dir);
+            }
+            job
+        }
+        None => Job::default(),
+    };
+    // Flags override the job file, which overrides the environment
+    let common_file = match take_option(&mut args, "--common-file")? {
+        Some(file) => Some(file.to_string()),
+        None => job
+            .common_file
+            .or_else(|| env::var("VCRY_COMMON_FILE").ok()),
+    }
+    .map(|file| file::expand_home(&file).into_owned());
+    let masters = match take_option(&mut args, "--range")? {
+        Some(range) => Some(parse_range(range)?),
+        None if job.min.is_some() || job.max.is_some() => {
+            Some(job.min.unwrap_or(0)..job.max.unwrap_or(pins::MAX_PIN) + 1)
+        }
+        None => None,
+    };
+    let output_file = match take_option(&mut args, "--output")? {
+        Some(file) => Some(file.to_string()),
+        None => job.output,
+    }
+    .map(|file| file::expand_home(&file).into_owned());
+    let master_file = take_option(&mut args, "--master-file")?.map(file::expand_home);
+    let master = |arg: &[&str]| {
+        let master = read_master(arg.first().copied(), master_file.as_deref())?;
+        check_master(master, allow_weak_master)
+    };
+    let scan = |thread_count| -> Result<ScanOptions> {
+        let clamped = re::clamp_thread_count(thread_count)?;
+        if clamped < thread_count && output != Output::Porcelain {
+            eprintln!(">> Lowering thread count from {thread_count} to {clamped}.");
         }
+        Ok(ScanOptions {
+            thread_count: clamped,
+            spill,
+            rate_limit,
+            yes,
+            output,
+            max_results,
+            output_ids: output_ids.clone(),
+            output_file: output_file.clone(),
+        })
+    };
+    // A quoted ~ reaches vcry as it is, so file arguments are expanded here
+    let file_positions = file_positions(&args);
+    let expanded: Vec<_> = args
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| {
+            if file_positions.contains(&i) {
+                file::expand_home(arg)
+            } else {
+                Cow::Borrowed(*arg)
+            }
+        })
+        .collect();
+    let args: Vec<&str> = expanded.iter().map(|arg| arg.as_ref()).collect();
+    let app = |file: &str, bytes| {
+        let mut app = App::new(file.to_string(), bytes)
+            .with_reveal_timeout(reveal_timeout)
+            .with_clipboard_timeout(clipboard_timeout)
+            .with_pin_policy(pin_policy);
+        if let Some(seed) = seed {
+            app = app.with_seed(seed);
+        }
+        if let Some(id) = select {
+            app = app.with_selection(id);
+        }
+        if lock_on_focus_loss {
+            app = app.with_lock_on_focus_loss();
+        }
+        if debug {
+            app = app.with_debug();
+        }
+        if allow_weak_master {
+            app = app.with_weak_master_allowed();
+        }
+        if track_usage {
+            app = app.with_usage_tracking();
+        }
+        app
+    };
+    match args.as_slice() {
+        ["crack" | "c", file] => crack(
+            file,
+            score_mode,
+            common_file.as_deref(),
+            masters,
+            &scan(job.threads.unwrap_or_else(default_thread_count))?,
+        ),
+        ["crack" | "c", file, thread_count] => crack(
+            file,
+            score_mode,
+            common_file.as_deref(),
+            masters,
+            &scan(thread_count.parse()?)?,
+        ),
+        ["find" | "f", file, thread_count, known_pins @ ..] => {
+            let thread_count: u32 = thread_count.parse()?;
+            let mut pins = Vec::new();
+            for pin in known_pins {
+                pins.push(pin.parse()?);
+            }
+            find(file, &pins, ids.as_deref(), &scan(thread_count)?)
+        }
+        ["export" | "e", "--format", format, file, arg @ ..] if arg.len() <= 1 => {
+            let Some(format) = ExportFormat::parse(format) else {
+                bail!("Unknown export format: {format}");
+            };
+            export(file, master(arg)?, format)
+        }
+        ["verify", file] => verify(file),
+        ["check", file, "-"] => check_stdin(file),
+        ["check", file, arg @ ..] if arg.len() <= 1 => check(file, master(arg)?),
+        ["info" | "i", file, arg @ ..] if arg.len() <= 1 => info(file, master(arg)?, free),
+        ["describe", file, arg, metadata] => describe(file, master(&[arg])?, metadata),
+        ["describe", file, metadata] => describe(file, master(&[])?, metadata),
+        ["duress", file, arg, duress_master] => {
+            let duress_master = check_master(duress_master.parse()?, allow_weak_master)?;
+            duress(file, master(&[arg])?, duress_master)
+        }
+        ["duress", file, duress_master] => {
+            let duress_master = check_master(duress_master.parse()?, allow_weak_master)?;
+            duress(file, master(&[])?, duress_master)
+        }
+        ["qr", file, arg, id] => qr(file, master(&[arg])?, id.parse()?),
+        ["qr", file, id] => qr(file, master(&[])?, id.parse()?),
+        ["history", file, arg, id] => history(file, master(&[arg])?, id.parse()?),
+        ["history", file, id] => history(file, master(&[])?, id.parse()?),
+        ["which", file, arg, value] => which(file, master(&[arg])?, value),
+        ["which", file, value] => which(file, master(&[])?, value),
+        ["replace", file, arg, old, new] => {
+            replace(file, master(&[arg])?, old.parse()?, new.parse()?)
+        }
+        ["replace", file, old, new] => replace(file, master(&[])?, old.parse()?, new.parse()?),
+        ["audit" | "a", file, arg @ ..] if arg.len() <= 1 => audit(file, master(arg)?, group, json),
+        ["estimate", file] => estimate(file, scan(default_thread_count())?.thread_count, json),
+        ["estimate", file, thread_count] => {
+            estimate(file, scan(thread_count.parse()?)?.thread_count, json)
+        }
+        ["compact", file, arg @ ..] if arg.len() <= 1 => compact(file, master(arg)?, yes),
+        ["split", file, arg, count] => split(file, master(&[arg])?, count.parse()?),
+        ["split", file, count] => split(file, master(&[])?, count.parse()?),
+        ["match" | "m", file, candidates] => match_candidates(file, candidates),
+        ["version"] => version(),
+        ["analyze-master", master] => analyze_master(master.parse()?),
+        ["encrypt-pin", master, id, pin] => encrypt_pin(master.parse()?, id.parse()?, pin.parse()?),
+        ["decrypt-pin", master, id, raw] => decrypt_pin(master.parse()?, id.parse()?, raw),
+        ["recover", file] => {
+            let options = scan(default_thread_count())?;
+            let bytes = read_file("vault file", file)?;
+            let mut cracker = Cracker::load(&bytes)?.with_quiet();
+            if let Some(percent) = options.rate_limit {
+                cracker = cracker.with_rate_limit(percent);
+            }
+            let mut terminal = tui::init(&format!("vcry — recover {file}"), lock_on_focus_loss)?;
+            let app_result = RecoverApp::new(cracker, options.thread_count)
+                .run(&mut terminal)
+                .and_then(|master| match master {
+                    Some(master) => app(file, bytes).with_master(master).run(&mut terminal),
+                    None => Ok(()),
+                });
+            tui::restore()?;
+            app_result
+        }
+        ["open" | "o", file] | [file] => {
+            let path = std::path::Path::new(&file);
+            let bytes = if path.exists() {
+                read_file("vault file", file)?
+            } else {
+                vec![0x00]
+            };
+            Pins::verify(&bytes)?;
+            let mut terminal = tui::init(&format!("vcry — {file}"), lock_on_focus_loss)?;
+            let app_result = app(file, bytes).run(&mut terminal);
+            tui::restore()?;
+            app_result
+        }
+        _ => bail!(
+            "    Usage
+vcry crack [--validity-only] [--job <file>] [--common-file <file>] [--range <min>-<max>] [--output <file>] [--spill] [--limit-rate <percent>] [--max-results <count>] [--output-ids <ids>] [--yes] [--json | --porcelain] <file>
+vcry crack [--validity-only] [--job <file>] [--common-file <file>] [--range <min>-<max>] [--output <file>] [--spill] [--limit-rate <percent>] [--max-results <count>] [--output-ids <ids>] [--yes] [--json | --porcelain] <file> <thread count>
+vcry find [--output <file>] [--spill] [--limit-rate <percent>] [--max-results <count>] [--ids <ids>] [--output-ids <ids>] [--yes] [--json | --porcelain] <file> <thread count> <known pins...>
+vcry verify <file>
+vcry check <file> [master | -]
+vcry info [--free] <file> [master]
+vcry describe [--dry-run] <file> [master] <metadata>
+vcry duress [--dry-run] <file> [master] <duress master>
+vcry audit [--group] [--json] <file> [master]
+vcry estimate [--json] <file> [thread count]
+vcry history <file> [master] <id>
+vcry which <file> [master] <value>
+vcry qr <file> [master] <id>
+vcry replace [--dry-run] <file> [master] <old value> <new value>
+vcry match <file> <crack output>
+vcry recover [--limit-rate <percent>] <file>
+vcry analyze-master <master>
+vcry version
+vcry encrypt-pin <master> <id> <pin>
+vcry decrypt-pin <master> <id> <raw hex>
+vcry split [--dry-run] <file> [master] <count>
+vcry compact [--dry-run] [--yes] <file> [master]
+vcry export --format keepass-csv <file> [master]
+vcry open <file> [--reveal-secs <seconds>] [--seed <seed>] [--select <id>] [--lock-on-focus-loss] [--pin-length <digits>] [--allow-weak] [--clipboard-secs <seconds>] [--debug] [--allow-weak-master] [--track-usage]
+vcry <file> [--reveal-secs <seconds>] [--seed <seed>] [--select <id>] [--lock-on-focus-loss] [--pin-length <digits>] [--allow-weak] [--clipboard-secs <seconds>] [--debug] [--allow-weak-master] [--track-usage]
+
+A leading ~ or ~user in a file path is expanded to that home directory,
+even when quoted.
+Master 0 is rejected, since it leaves the pins unencrypted, unless
+--allow-weak-master is given for tests.
+--strict fails a command, with exit code 1, instead of going on when
+  - the master is weak, like a weak pin would be,
+  - or the vault it loads has a weak, reused or expired pin, or fewer than
+    10 ids left.
+It cannot be combined with --allow-weak-master. Metadata or text secrets
+that are not valid text and pin counts that do not match the file fail
+every command that loads a vault, with or without --strict. The TUI is
+not affected by --strict.
+The master can be left out when it is read from a file with
+--master-file <file>, which must contain just the master pin. Otherwise it
+is asked for without echoing when stdin is a terminal.
+Generated pins have 9 digits, or as many as --pin-length says, and are
+drawn again while they are weak unless --allow-weak is given.
+--seed (or VCRY_SEED) makes generated pins reproducible. It is meant for
+tests and demos only, seeded pins are as guessable as the seed.
+Without a thread count, crack uses VCRY_THREADS threads, or 4 if that is
+unset or not a number. Thread counts above 4 per core are lowered to that.
+--common-file (or VCRY_COMMON_FILE) replaces the pins crack looks for with
+the ones listed in the file, one per line.
+--range <min>-<max> makes crack scan only the masters from min to max.
+--output <file> makes crack and find write their results to the file
+instead of stdout.
+--job <file> reads the thread count, range, common pins file and output
+file of a crack run from a TOML file with the keys threads, min, max,
+common_file and output, all optional. Paths in it are relative to the job
+file. Flags and arguments override it, and it overrides VCRY_THREADS and
+VCRY_COMMON_FILE.
+On a terminal, crack and find color each master by its score, green for
+at least half the pins, yellow for a quarter, red below. NO_COLOR turns
+this off.
+Before scanning, crack and find estimate how long it will take and ask
+for confirmation if that is over 10 minutes. --yes skips the question,
+without it they refuse to start when there is no terminal to ask on.
+--ids <id>,<id>,... makes find consider only the pins with those ids, so the
+other pins may be corrupt or unknown.
+--output-ids <id>,<id>,... makes crack and find print the pins with those
+ids as each master decrypts them, or ? where they do not decrypt to a pin.
+--limit-rate <percent> makes crack and find sleep between batches so that
+each thread is busy only about that percentage of the time. The resulting
+CPU usage is approximate.
+--max-results <count> caps the suspicious masters crack and find keep in
+memory, 10000000 by default. Masters over the cap are dropped with a
+warning, even with --porcelain. --spill keeps results on disk instead and
+is not capped.
+crack and find end by printing how many candidates they scanned, in how
+long and how many that makes per second to stderr, to compare runs and
+hardware.
+find prints no masters when it finds more than 1000, unless they go to a
+file with --output or are printed with --json or --porcelain.
+--json makes crack and find print one JSON object per master, followed by
+a summary with the candidates scanned, elapsed seconds, candidates per
+second, thread count, number of results and whether they were truncated.
+--porcelain makes crack and find print one <master><TAB><score> line per
+master, followed by an <id>=<pin> column per --output-ids id, and nothing
+on stderr but errors. This is the stable format for
+scripts and will not change between versions.
+estimate times a short scan of a vault and extrapolates how long cracking
+its master takes, rating the risk as critical (under an hour), high (under
+a day) or moderate. --json prints this as one JSON object.
+check tells whether a master opens the vault, meaning every pin decrypts
+to a valid one. With - it reads candidate masters from stdin instead, one
+per line as the first column of crack or find output, and prints those that
+open it, so that `vcry crack --porcelain <file> | vcry check <file> -`
+leaves only masters worth trying. It fails with exit code 2 if none do.
+audit also lists the pins that expired or expire within 7 days. --group
+sorts the pins into weak, reused and ok ones instead of rating each, plus
+the expired and expiring ones. --json prints these groups as JSON.
+--dry-run makes describe, duress, replace, split and compact print what they would
+change instead of writing any file.
+--deterministic clears the two random obfuscation bits of every pin when
+saving or running encrypt-pin, so saving the same pins always writes the
+same bytes. It is meant for golden files in tests and stable backups, not
+for security, and the random order of duress sections still varies.
+C copies the selected pin with wl-copy, xclip or pbcopy. It is cleared
+from the clipboard after 20 seconds, or --clipboard-secs, and on quitting,
+unless something else was copied since.
+--track-usage records when each pin is copied and shows how many days ago
+that was, to spot pins that are no longer used. Copying then counts as a
+change to save.
+X sets the selected pin to expire in a number of days, or clears its expiry
+when none are entered. Expired pins are marked red, ones expiring within 7
+days yellow.
+--lock-on-focus-loss (or VCRY_LOCK_ON_FOCUS_LOSS=1) locks the vault when
+the terminal loses focus. Not every terminal reports focus changes.
+--debug lets Shift-D show a panel with the selected pin's keystream, its
+encryption under the master and its stored bytes, for looking into damaged
+vaults. It shows secrets in a form that helps crack them, so keep it off
+otherwise.
+-v/--verbose prints how long loading, saving and cracking took to stderr,
+and annotates the masters crack and find print with their hex form and
+whether they look like a date.
+--backups <count> (or VCRY_BACKUPS) keeps the previous versions of a vault
+as <file>.bak.1 (newest) to <file>.bak.<count> whenever it is overwritten.
+To restore one, copy it back over <file>. Backups are off by default.
+history prints the previous values of a pin, newest first. The last few
+are kept whenever a pin is generated, edited or cleared.
+which prints the ids of the pins or text secrets holding a value, one per
+line, or Not found if there are none.
+compact renumbers the pins from 0 in their current order. It asks first,
+since anything referring to a pin by id may then point at another one,
+and --yes skips the question.
+split deals the pins out in turn to new vaults <file>.1 to <file>.<count>,
+each with the metadata. Text secrets, histories, expiry and last use times
+go along with their pins. Vaults with a duress master cannot be split.
+recover asks for pins remembered from the vault, searches every master for
+them like find, with VCRY_THREADS threads, and offers to open the vault
+with one of the masters it finds, best first.
+duress adds a second master that opens an empty set of decoy pins, which
+can be filled like any other vault. Anyone holding the file can still tell
+that it has two sets of pins, so this only helps when forced to unlock it.
+
+    Exit codes
+0 success, 1 any other error, 2 wrong master, 3 malformed vault,
+4 file not found"
+        ),
+    }
+}
+
+/// Parses the master given as an argument or, failing that, read from
+/// `master_file`
+fn read_master(arg: Option<&str>, master_file: Option<&str>) -> Result<u32> {
+    if let Some(arg) = arg {
+        return Ok(arg.parse()?);
+    }
+    let Some(master_file) = master_file else {
+        if io::stdin().is_terminal() {
+            return prompt_master();
+        }
+        bail!("Missing master, pass it as an argument or with --master-file");
+    };
+    let contents = read_text("master file", master_file)?;
+    match contents.trim().parse() {
+        Ok(master) if master <= pins::MAX_PIN => Ok(master),
+        _ => bail!("Master file {master_file} does not contain a master pin"),
+    }
+}
+
+/// Asks for the master on the terminal without echoing it
+fn prompt_master() -> Result<u32> {
+    eprint!("Master: ");
+    crossterm::terminal::enable_raw_mode()?;
+    let input = (|| {
+        let mut input = String::new();
+        loop {
+            let Event::Key(key_event) = event::read()? else {
+                continue;
+            };
+            if key_event.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key_event.code {
+                KeyCode::Enter if !input.is_empty() => return Ok(input),
+                KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    bail!("Cancelled")
+                }
+                KeyCode::Esc => bail!("Cancelled"),
+                KeyCode::Char(c @ '0'..='9') if input.len() < 9 => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                _ => (),
+            }
+        }
+    })();
+    crossterm::terminal::disable_raw_mode()?;
+    eprintln!();
+    Ok(input?.parse()?)
+}
+
+/// Reads the file at `path`, naming `what` it should be in errors
+fn read_file(what: &str, path: &str) -> Result<Vec<u8>> {
+    if std::path::Path::new(path).is_dir() {
+        bail!("Could not read {what} '{path}': it is a directory");
+    }
+    fs::read(path).with_context(|| format!("Could not read {what} '{path}'"))
+}
+
+/// Like `read_file`, for files that must hold text
+fn read_text(what: &str, path: &str) -> Result<String> {
+    String::from_utf8(read_file(what, path)?)
+        .with_context(|| format!("Could not read {what} '{path}': it is not text"))
+}
+
+/// Runs `f`, printing how long it took in verbose mode
+fn timed<T>(operation: &str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    if VERBOSE.load(Ordering::Relaxed) {
+        eprintln!(">> {operation} took {:.2?}", start.elapsed());
+    }
+    result
+}
+
+/// Encrypts the pins for writing, with cleared obfuscation bits in
+/// deterministic mode
+fn save(pins: &Pins) -> Result<Vec<u8>> {
+    timed("save", || {
+        if DETERMINISTIC.load(Ordering::Relaxed) {
+            pins.save_with(&mut StepRng::new(0, 0))
+        } else {
+            pins.save()
+        }
+    })
+}
+
+/// Overwrites a vault, keeping backups of the previous version if enabled
+fn write_vault(path: &str, bytes: &[u8]) -> Result<()> {
+    file::rotate_backups(path, BACKUPS.load(Ordering::Relaxed))?;
+    file::write_atomic(path, bytes)?;
+    Ok(())
+}
+
+/// Saves changed pins over the vault they were loaded from, or in dry-run
+/// mode prints the changes instead
+fn write_changes(path: &str, master: u32, pins: &Pins) -> Result<()> {
+    if !DRY_RUN.load(Ordering::Relaxed) {
+        return write_vault(path, &save(pins)?);
+    }
+    let before = load(path, master)?;
+    let changes = diff(&before, pins);
+    if changes.is_empty() {
+        println!("No changes");
+    }
+    for change in changes {
+        println!("{change}");
+    }
+    eprintln!(">> Dry run, {path} was not written.");
+    Ok(())
+}
+
+/// Describes how `after` differs from `before`, one line per change
+fn diff(before: &Pins, after: &Pins) -> Vec<String> {
+    let value = |pins: &Pins, id: u8| {
+        let pin = pins.iter().find(|pin| pin.id == id)?;
+        Some(match pins.text(id) {
+            Some(text) => format!("{text:?}"),
+            None if pin.pin == 0 => "(empty)".to_string(),
+            None => pin.pin.to_string(),
+        })
+    };
+    let mut ids: Vec<_> = before.iter_ids().chain(after.iter_ids()).collect();
+    ids.sort_unstable();
+    ids.dedup();
+    let mut changes = Vec::new();
+    for id in ids {
+        match (value(before, id), value(after, id)) {
+            (Some(old), Some(new)) if old != new => {
+                changes.push(format!("~ Pin {id:2}: {old} -> {new}"));
+            }
+            (Some(old), None) => changes.push(format!("- Pin {id:2}: {old}")),
+            (None, Some(new)) => changes.push(format!("+ Pin {id:2}: {new}")),
+            _ => (),
+        }
+    }
+    if before.metadata() != after.metadata() {
+        changes.push(format!(
+            "~ Metadata: {:?} -> {:?}",
+            before.metadata(),
+            after.metadata()
+        ));
+    }
+    if !before.has_duress() && after.has_duress() {
+        changes.push("+ Duress master".to_string());
+    }
+    changes
+}
+
+/// Thread count from VCRY_THREADS, falling back to `DEFAULT_THREAD_COUNT`
+fn default_thread_count() -> u32 {
+    let Ok(threads) = env::var("VCRY_THREADS") else {
+        return DEFAULT_THREAD_COUNT;
+    };
+    match threads.parse() {
+        Ok(thread_count @ 1..) => thread_count,
+        _ => {
+            eprintln!(">> Ignoring invalid VCRY_THREADS: {threads}");
+            DEFAULT_THREAD_COUNT
+        }
+    }
+}
+
+/// Parses a `<min>-<max>` range of masters, both included
+fn parse_range(range: &str) -> Result<Range<u32>> {
+    let parsed = range
+        .split_once('-')
+        .and_then(|(min, max)| Some((min.parse::<u32>().ok()?, max.parse::<u32>().ok()?)));
+    match parsed {
+        Some((min, max)) if min <= max && max <= pins::MAX_PIN => Ok(min..max + 1),
+        _ => bail!(
+            "--range must be <min>-<max> with min <= max <= {}",
+            pins::MAX_PIN
+        ),
+    }
+}
+
+/// Passes `master` through unless it leaves the pins unencrypted and that is
+/// not allowed
+fn check_master(master: u32, allow_weak_master: bool) -> Result<u32> {
+    if let Err(err) = pins::check_master(master, allow_weak_master) {
+        bail!("{err}. Pass --allow-weak-master to use it anyway, for tests only");
+    }
+    match pins::classify(master) {
+        WeakKind::Ok => (),
+        kind if STRICT.load(Ordering::Relaxed) => bail!("Master is weak: {kind}"),
+        _ => (),
+    }
+    Ok(master)
+}
+
+/// Removes `flag` from `args`, returning whether it was present
+fn take_flag(args: &mut Vec<&str>, flag: &str) -> bool {
+    let Some(i) = args.iter().position(|arg| *arg == flag) else {
+        return false;
+    };
+    args.remove(i);
+    true
+}
+
+/// Positions of the file arguments in a command line without its flags
+fn file_positions(args: &[&str]) -> Vec<usize> {
+    match args {
+        ["analyze-master" | "encrypt-pin" | "decrypt-pin" | "version", ..] => vec![],
+        ["match" | "m", ..] => vec![1, 2],
+        ["export" | "e", "--format", ..] => vec![3],
+        [_] => vec![0],
+        _ => vec![1],
+    }
+}
+
+/// Removes `option` and the value following it from `args`
+fn take_option<'a>(args: &mut Vec<&'a str>, option: &str) -> Result<Option<&'a str>> {
+    let Some(i) = args.iter().position(|arg| *arg == option) else {
+        return Ok(None);
+    };
+    if i + 1 >= args.len() {
+        bail!("Missing value for {option}");
+    }
+    let value = args.remove(i + 1);
+    args.remove(i);
+    Ok(Some(value))
+}
+
+/// Options shared by `crack` and `find`
+struct ScanOptions {
+    thread_count: u32,
+    /// Spill results to temporary files instead of keeping them in memory
+    spill: bool,
+    rate_limit: Option<u8>,
+    /// Skip asking before long scans
+    yes: bool,
+    output: Output,
+    /// Most suspicious masters to keep in memory, if not the default
+    max_results: Option<usize>,
+    /// Ids of the pins to decrypt and print along with each master
+    output_ids: Vec<u8>,
+    /// File to write the results to instead of stdout
+    output_file: Option<String>,
+}
+
+impl ScanOptions {
+    /// Whether to report progress on stderr
+    fn chatty(&self) -> bool {
+        self.output != Output::Porcelain
+    }
+}
+
+/// How `SusWriter` prints masters
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Output {
+    /// `Display` of `SusMaster`, colored on a terminal
+    Human,
+    /// JSON lines, followed by a summary of the scan
+    Json,
+    /// `master<TAB>score` lines and nothing on stderr, which stays stable
+    /// across versions
+    Porcelain,
+}
+
+fn crack(
+    file: &str,
+    score_mode: ScoreMode,
+    common_file: Option<&str>,
+    masters: Option<Range<u32>>,
+    options: &ScanOptions,
+) -> Result<()> {
+    let thread_count = options.thread_count;
+    let bytes = read_file("vault file", file)?;
+    let mut cracker = Cracker::load(&bytes)?;
+    if let Some(masters) = masters {
+        cracker = cracker.with_masters(masters);
+    }
+    if let Some(percent) = options.rate_limit {
+        cracker = cracker.with_rate_limit(percent);
+    }
+    if let Some(max_results) = options.max_results {
+        cracker = cracker.with_max_results(max_results);
+    }
+    if let Some(common_file) = common_file {
+        let common = re::parse_common_pins(&read_text("common pins file", common_file)?)?;
+        cracker = cracker.with_common_pins(common);
+    }
+    if !options.chatty() {
+        cracker = cracker.with_quiet();
+    }
+    // Asked first, so that declining leaves no empty output file behind
+    if !options.yes {
+        confirm_scan(&cracker, thread_count)?;
+    }
+    let mut out = SusWriter::new(&cracker, options)?;
+    if options.chatty() {
+        eprintln!(">> Cracking vault with {thread_count} thread(s).");
+    }
+    let start = Instant::now();
+    if options.spill {
+        let spill = timed("crack", || {
+            cracker.bruteforce_spilled(thread_count, score_mode, &env::temp_dir())
+        })?;
+        return print_spill(&spill, out, options, start);
+    }
+    let scanned = AtomicU64::new(0);
+    let sus_pins = timed("crack", || {
+        cracker.bruteforce_threaded_with_progress(thread_count, score_mode, &|total| {
+            scanned.fetch_max(total, Ordering::Relaxed);
+        })
+    });
+    if options.chatty() {
+        eprintln!(">> Done. Found {} suspicious master pins.", sus_pins.len());
+    }
+    if cracker.truncated() {
+        eprintln!(">> Results truncated at {}.", cracker.max_results());
+    }
+    for sus in &sus_pins {
+        out.write(sus)?;
+    }
+    out.summary(
+        scanned.into_inner(),
+        start,
+        thread_count,
+        sus_pins.len(),
+        cracker.truncated(),
+    )?;
+    out.finish()?;
+    Ok(())
+}
+
+fn find(file: &str, known_pins: &[u32], ids: Option<&[u8]>, options: &ScanOptions) -> Result<()> {
+    let thread_count = options.thread_count;
+    let bytes = read_file("vault file", file)?;
+    let mut cracker = Cracker::load(&bytes)?;
+    if let Some(ids) = ids {
+        cracker = cracker.with_ids(ids)?;
+    }
+    if let Some(percent) = options.rate_limit {
+        cracker = cracker.with_rate_limit(percent);
+    }
+    if let Some(max_results) = options.max_results {
+        cracker = cracker.with_max_results(max_results);
+    }
+    if !options.chatty() {
+        cracker = cracker.with_quiet();
+    }
+    // Asked first, so that declining leaves no empty output file behind
+    if !options.yes {
+        confirm_scan(&cracker, thread_count)?;
+    }
+    let mut out = SusWriter::new(&cracker, options)?;
+    if options.chatty() {
+        eprintln!(">> Finding pins in vault with {thread_count} thread(s).");
+    }
+    let start = Instant::now();
+    if options.spill {
+        let spill = timed("find", || {
+            cracker.find_spilled(thread_count, known_pins, &env::temp_dir())
+        })?;
+        return print_spill(&spill, out, options, start);
+    }
+    let scanned = AtomicU64::new(0);
+    let sus_pins = timed("find", || {
+        cracker.find_threaded_with_progress(thread_count, known_pins, &|total| {
+            scanned.fetch_max(total, Ordering::Relaxed);
+        })
+    });
+    if options.chatty() {
+        eprintln!(">> Done. Found {} suspicious master pins.", sus_pins.len());
+    }
+    if cracker.truncated() {
+        eprintln!(">> Results truncated at {}.", cracker.max_results());
+    }
+    // Long lists are of no use on a terminal, but scripts and files get them
+    let listed = options.output != Output::Human || options.output_file.is_some();
+    if listed || sus_pins.len() <= FIND_PRINT_LIMIT {
+        for sus in &sus_pins {
+            out.write(sus)?;
+        }
+    } else {
+        eprintln!(
+            ">> Not printing {} masters, pass --output <file> to keep them.",
+            sus_pins.len()
+        );
+    }
+    out.summary(
+        scanned.into_inner(),
+        start,
+        thread_count,
+        sus_pins.len(),
+        cracker.truncated(),
+    )?;
+    out.finish()?;
+    Ok(())
+}
+
+/// Estimates how long scanning every master takes and, if that is longer than
+/// `CONFIRM_AFTER`, asks whether to go ahead
+///
+/// Without a terminal to ask on, the scan is refused instead.
+fn confirm_scan(cracker: &Cracker, thread_count: u32) -> Result<()> {
+    // Timing a sample would take as long as scanning this few masters
+    if cracker.master_count() <= SAMPLE_SIZE {
+        return Ok(());
+    }
+    let estimate =
+        sample_scan(cracker) * (cracker.master_count() / SAMPLE_SIZE + 1) / thread_count.max(1);
+    if estimate <= CONFIRM_AFTER {
+        return Ok(());
+    }
+    let estimate = format_duration(estimate);
+    if !io::stdin().is_terminal() {
+        bail!("Scanning would take about {estimate}, pass --yes to start anyway");
+    }
+    confirm(&format!(
+        "Scanning will take about {estimate} with {thread_count} thread(s)."
+    ))
+}
+
+/// Times scanning `SAMPLE_SIZE` masters on one thread
+fn sample_scan(cracker: &Cracker) -> Duration {
+    let start = Instant::now();
+    cracker.bruteforce_window(1, ScoreMode::CommonPins, 0..SAMPLE_SIZE, Partition::Strided);
+    start.elapsed()
+}
+
+/// Reports how long cracking a vault would take, from a short sample scan
+///
+/// Every master fits in 30 bits, so no vault takes long to crack. The rating
+/// only tells how soon one falls.
+fn estimate(file: &str, thread_count: u32, json: bool) -> Result<()> {
+    let bytes = read_file("vault file", file)?;
+    let cracker = Cracker::load(&bytes)?.with_quiet();
+    let sample = sample_scan(&cracker);
+    let masters = pins::MAX_PIN as u64 + 1;
+    let rate = SAMPLE_SIZE as f64 / sample.as_secs_f64().max(f64::EPSILON);
+    // Threads beyond the cores take turns rather than adding throughput
+    let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get() as u32);
+    let parallel = thread_count.min(cores);
+    let estimate = Duration::from_secs_f64(masters as f64 / rate / parallel as f64);
+    let risk = if estimate < Duration::from_secs(60 * 60) {
+        "critical"
+    } else if estimate < Duration::from_secs(24 * 60 * 60) {
+        "high"
+    } else {
+        "moderate"
+    };
+    if json {
+        println!(
+            r#"{{"sampled":{SAMPLE_SIZE},"masters":{masters},"masters_per_sec":{rate:.0},"threads":{thread_count},"estimate_secs":{:.0},"risk":"{risk}"}}"#,
+            estimate.as_secs_f64()
+        );
+        return Ok(());
+    }
+    println!(
+        "Sampled {SAMPLE_SIZE} masters at {:.1}M masters/s per thread",
+        rate / 1e6
+    );
+    println!(
+        "Trying all {masters} masters with {thread_count} thread(s) on {cores} core(s) takes about {}",
+        format_duration(estimate)
+    );
+    println!("Risk: {risk}");
+    Ok(())
+}
+
+/// Asks whether to go on after `question`, failing unless the answer is yes
+fn confirm(question: &str) -> Result<()> {
+    eprint!(">> {question} Continue? [y/N] ");
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    if !answer.trim().eq_ignore_ascii_case("y") {
+        bail!("Cancelled");
+    }
+    Ok(())
+}
+
+/// Formats a long duration in hours and minutes, or minutes and seconds
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h {}m", secs / 3600, secs % 3600 / 60)
+    } else {
+        format!("{}m {}s", secs / 60, secs % 60)
+    }
+}
+
+fn match_candidates(file: &str, candidates: &str) -> Result<()> {
+    let bytes = read_file("vault file", file)?;
+    let cracker = Cracker::load(&bytes)?;
+    let mut masters = Vec::new();
+    for line in read_text("candidates file", candidates)?.lines() {
+        if let Some(master) = line.split_whitespace().next() {
+            masters.push(master.parse()?);
+        }
+    }
+    let mut terminal = tui::init(&format!("vcry — {file}"), false)?;
+    let app_result = MatchApp::new(cracker, masters).run(&mut terminal);
+    tui::restore()?;
+    app_result
+}
+
+/// Prints spilled results ordered by master rather than score, so that they
+/// never have to be held in memory at once
+fn print_spill(
+    spill: &Spill,
+    mut out: SusWriter,
+    options: &ScanOptions,
+    start: Instant,
+) -> Result<()> {
+    let count = spill.merge(|sus| out.write(&sus))?;
+    if options.chatty() {
+        eprintln!(">> Done. Found {count} suspicious master pins.");
+    }
+    out.summary(spill.scanned(), start, options.thread_count, count, false)?;
+    out.finish()?;
+    Ok(())
+}
+
+/// Prints suspicious masters to stdout
+///
+/// In verbose mode each master is annotated with its hex form and whether it
+/// looks like a date. On a terminal, unless `NO_COLOR` is set, masters are
+/// colored by how close their score comes to the highest possible one.
+///
+/// In JSON mode each master is a `{"master":…,"score":…}` line instead, and
+/// in porcelain mode a `master<TAB>score` line, both without annotations or
+/// color.
+///
+/// With `--output-ids`, the pins with those ids are decrypted under each
+/// master and printed after it, as `[pins id=pin …]`, a `"pins"` object or
+/// `id=pin` columns. Pins that do not decrypt validly are shown as `?` or
+/// `null`.
+struct SusWriter {
+    out: Box<dyn Write>,
+    /// Highest possible score, if coloring
+    max_score: Option<u32>,
+    output: Output,
+    /// Stored form of the pins to print with each master, by id
+    raw_pins: Vec<(u8, u32)>,
+}
+
+impl SusWriter {
+    fn new(cracker: &Cracker, options: &ScanOptions) -> Result<Self> {
+        let mut raw_pins = Vec::new();
+        for &id in &options.output_ids {
+            let Some(raw_pin) = cracker.raw_pin(id) else {
+                bail!("Vault has no pin with id {id}");
+            };
+            raw_pins.push((id, raw_pin));
+        }
+        let out: Box<dyn Write> = match &options.output_file {
+            Some(path) => {
+                Box::new(BufWriter::new(File::create(path).with_context(|| {
+                    format!("Could not create output file '{path}'")
+                })?))
+            }
+            None => Box::new(io::stdout().lock()),
+        };
+        let color = options.output == Output::Human
+            && options.output_file.is_none()
+            && io::stdout().is_terminal()
+            && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty());
+        Ok(Self {
+            out,
+            max_score: color.then_some(cracker.pin_count() as u32),
+            output: options.output,
+            raw_pins,
+        })
+    }
+
+    /// The pins to print with `master`, `None` where one does not decrypt
+    /// validly
+    fn pins(&self, master: u32) -> impl Iterator<Item = (u8, Option<u32>)> + '_ {
+        self.raw_pins.iter().map(move |&(id, raw_pin)| {
+            let pin = pins::decrypt(master, id, raw_pin);
+            (id, (pin <= pins::MAX_PIN).then_some(pin))
+        })
+    }
+
+    /// Flushes what is left of the results to the output file
+    fn finish(mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    fn write(&mut self, sus: &SusMaster) -> io::Result<()> {
+        let show = |pin: Option<u32>| pin.map_or("?".to_string(), |pin| pin.to_string());
+        match self.output {
+            Output::Json => {
+                let mut line = format!(r#"{{"master":{},"score":{}"#, sus.master, sus.score);
+                if !self.raw_pins.is_empty() {
+                    let pins: Vec<_> = self
+                        .pins(sus.master)
+                        .map(|(id, pin)| {
+                            format!(
+                                r#""{id}":{}"#,
+                                pin.map_or("null".to_string(), |pin| pin.to_string())
+                            )
+                        })
+                        .collect();
+                    line += &format!(r#","pins":{{{}}}"#, pins.join(","));
+                }
+                return writeln!(self.out, "{line}}}");
+            }
+            Output::Porcelain => {
+                let mut line = format!("{}\t{}", sus.master, sus.score);
+                for (id, pin) in self.pins(sus.master) {
+                    line += &format!("\t{id}={}", show(pin));
+                }
+                return writeln!(self.out, "{line}");
+            }
+            Output::Human => (),
+        }
+        let mut line = if VERBOSE.load(Ordering::Relaxed) {
+            format!("{sus:#}")
+        } else {
+            sus.to_string()
+        };
+        if !self.raw_pins.is_empty() {
+            let pins: Vec<_> = self
+                .pins(sus.master)
+                .map(|(id, pin)| format!("{id}={}", show(pin)))
+                .collect();
+            line += &format!(" [pins {}]", pins.join(" "));
+        }
+        let color = match self.max_score {
+            Some(max) if sus.score * 2 >= max => Color::Green,
+            Some(max) if sus.score * 4 >= max => Color::Yellow,
+            Some(_) => Color::Red,
+            None => return writeln!(self.out, "{line}"),
+        };
+        let style = ContentStyle {
+            foreground_color: Some(color),
+            ..ContentStyle::default()
+        };
+        writeln!(self.out, "{}", style.apply(line))
+    }
+
+    /// Writes the closing summary of a run that started at `start`, with how
+    /// many candidates it scanned per second
+    ///
+    /// The summary is a JSON line in JSON mode, goes to stderr otherwise and
+    /// is left out in porcelain mode.
+    fn summary(
+        &mut self,
+        scanned: u64,
+        start: Instant,
+        thread_count: u32,
+        results: usize,
+        truncated: bool,
+    ) -> io::Result<()> {
+        let elapsed = start.elapsed().as_secs_f64();
+        let per_sec = if elapsed > 0.0 {
+            scanned as f64 / elapsed
+        } else {
+            0.0
+        };
+        match self.output {
+            Output::Json => writeln!(
+                self.out,
+                r#"{{"summary":{{"scanned":{scanned},"elapsed_secs":{elapsed:.3},"candidates_per_sec":{per_sec:.0},"threads":{thread_count},"results":{results},"truncated":{truncated}}}}}"#,
+            ),
+            Output::Porcelain => Ok(()),
+            Output::Human => {
+                eprintln!(
+                    ">> Scanned {scanned} candidates in {elapsed:.2}s, {per_sec:.0} per second."
+                );
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Checks the structure of a vault without needing its master
+fn verify(file: &str) -> Result<()> {
+    let bytes = read_file("vault file", file)?;
+    Pins::verify(&bytes)?;
+    println!("OK");
+    Ok(())
+}
+
+/// Prints whether `master` opens the vault, failing with
+/// `VaultError::WrongMaster` if it does not
+fn check(file: &str, master: u32) -> Result<()> {
+    load(file, master)?;
+    println!("OK");
+    Ok(())
+}
+
+/// Prints the candidate masters read from stdin that open the vault, taking
+/// the first column of each line so that crack and find output can be piped
+/// in
+fn check_stdin(file: &str) -> Result<()> {
+    let bytes = read_file("vault file", file)?;
+    Pins::verify(&bytes)?;
+    let mut out = io::stdout().lock();
+    let mut found = false;
+    for (i, line) in io::stdin().lines().enumerate() {
+        let line = line?;
+        let Some(candidate) = line.split_whitespace().next() else {
+            continue;
+        };
+        let master: u32 = candidate
+            .parse()
+            .with_context(|| format!("Line {} does not start with a master", i + 1))?;
+        if Pins::load_checked(&bytes, master).is_ok() {
+            writeln!(out, "{master}")?;
+            found = true;
+        }
+    }
+    if !found {
+        bail!(VaultError::WrongMaster);
+    }
+    Ok(())
+}
+
+fn info(file: &str, master: u32, free: bool) -> Result<()> {
+    let bytes = read_file("vault file", file)?;
+    let pins = load(file, master)?;
+    println!("Format version: {}", pins::format_version(&bytes)?);
+    println!("Pins: {}", pins.len());
+    println!("Metadata: {}", pins.metadata());
+    if free {
+        println!("Free ids: {}", id_ranges(&pins.free_ids()));
+    }
+    Ok(())
+}
+
+/// Formats ascending ids, collapsing runs into ranges such as `3-7`
+fn id_ranges(ids: &[u8]) -> String {
+    let mut ranges: Vec<(u8, u8)> = Vec::new();
+    for &id in ids {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == id => *end = id,
+            _ => ranges.push((id, id)),
+        }
+    }
+    let ranges: Vec<_> = ranges
+        .into_iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{start}-{end}")
+            }
+        })
+        .collect();
+    ranges.join(", ")
+}
+
+/// Replaces the free text describing the vault
+fn describe(file: &str, master: u32, metadata: &str) -> Result<()> {
+    let mut pins = load(file, master)?;
+    pins.set_metadata(metadata);
+    write_changes(file, master, &pins)
+}
+
+/// Adds a master that opens decoy pins instead of the real ones
+fn duress(file: &str, master: u32, duress_master: u32) -> Result<()> {
+    if duress_master > pins::MAX_PIN {
+        bail!(
+            "Duress master is too large: {duress_master} > {}",
+            pins::MAX_PIN
+        );
+    }
+    let mut pins = load(file, master)?;
+    pins.add_duress(duress_master)?;
+    write_changes(file, master, &pins)
+}
+
+/// Sets every numeric pin holding `old` to `new`
+fn replace(file: &str, master: u32, old: u32, new: u32) -> Result<()> {
+    // Empty entries and text secrets hold 0 without it being their value
+    if old == 0 || new == 0 {
+        bail!("Pins cannot be replaced from or to 0, which marks empty entries");
+    }
+    if new > pins::MAX_PIN {
+        bail!("New value is too large: {new} > {}", pins::MAX_PIN);
+    }
+    let mut pins = load(file, master)?;
+    let matching: Vec<_> = (0..pins.len())
+        .filter(|&i| {
+            let pin = pins.get(i);
+            pins.kind(pin.id) == PinKind::Numeric && pin.pin == old
+        })
+        .collect();
+    for &i in &matching {
+        pins.set(i, new);
+    }
+    if !matching.is_empty() {
+        write_changes(file, master, &pins)?;
+    }
+    // A dry run lists the replaced pins instead
+    if matching.is_empty() || !DRY_RUN.load(Ordering::Relaxed) {
+        println!("Replaced {} pin(s)", matching.len());
+    }
+    Ok(())
+}
+
+/// Prints the ids of the pins whose value is `value`, which may also be the
+/// text of a text secret
+fn which(file: &str, master: u32, value: &str) -> Result<()> {
+    let pins = load(file, master)?;
+    // Empty pins hold 0 without it being their value
+    let number = value.parse::<u32>().ok().filter(|&number| number != 0);
+    let ids: Vec<_> = pins
+        .iter()
+        .filter(|pin| match pins.text(pin.id) {
+            Some(text) => text == value,
+            None => Some(pin.pin) == number,
+        })
+        .map(|pin| pin.id)
+        .collect();
+    if ids.is_empty() {
+        println!("Not found");
+    }
+    for id in ids {
+        println!("{id}");
+    }
+    Ok(())
+}
+
+fn export(file: &str, master: u32, format: ExportFormat) -> Result<()> {
+    let pins = load(file, master)?;
+    print!("{}", export::export(&pins, format));
+    Ok(())
+}
+
+/// Prints a pin as a QR code for scanning it with a phone
+fn qr(file: &str, master: u32, id: u8) -> Result<()> {
+    let pins = load(file, master)?;
+    let Some(pin) = pins.iter().find(|pin| pin.id == id) else {
+        bail!("No pin with id {id}");
+    };
+    if pins.kind(id) == PinKind::Text {
+        bail!("Pin {id} is a text secret, only numeric pins can be shown as a QR code");
+    }
+    if pin.pin == 0 {
+        bail!("Pin {id} is empty");
+    }
+    print!(
+        "{}",
+        QrCode::encode_numeric(&pin.pin.to_string())?.to_unicode()
+    );
+    Ok(())
+}
+
+/// Prints a pin followed by its previous values, newest first
+fn history(file: &str, master: u32, id: u8) -> Result<()> {
+    let pins = load(file, master)?;
+    let Some(pin) = pins.iter().find(|pin| pin.id == id) else {
+        bail!("No pin with id {id}");
+    };
+    match pins.text(id) {
+        Some(text) => println!("Pin {id:2}: {text}"),
+        None => println!("Pin {id:2}: {}", pin.pin),
+    }
+    for (i, value) in pins.history(id).iter().rev().enumerate() {
+        println!("    -{}: {value}", i + 1);
+    }
+    Ok(())
+}
+
+fn audit(file: &str, master: u32, group: bool, json: bool) -> Result<()> {
+    let pins = load(file, master)?;
+    if group || json {
+        return audit_groups(&pins, json);
+    }
+    let now = unix_now();
+    // Guessability only applies to numeric pins
+    let mut report: Vec<_> = pins
+        .iter()
+        .filter(|pin| pins.kind(pin.id) == PinKind::Numeric)
+        .map(|pin| (pin.id, pins::guessability(pin.pin), pins::classify(pin.pin)))
+        .collect();
+    report.sort_by_key(|&(id, score, _)| (score, id));
+    for (id, score, kind) in report {
+        let rating = match score {
+            0 => "very weak",
+            1 => "weak",
+            2 => "fair",
+            3 => "good",
+            _ => "strong",
+        };
+        match kind {
+            WeakKind::Ok => println!("Pin {id:2}: {score}/4 {rating}"),
+            kind => println!("Pin {id:2}: {score}/4 {rating} ({kind})"),
+        }
+    }
+    let (expired, expiring) = expiring_ids(&pins, now);
+    for id in expired {
+        println!("Pin {id:2}: expired, change it");
+    }
+    for id in expiring {
+        let days = pins
+            .expires(id)
+            .and_then(|expires| expiry_days(expires, now));
+        println!("Pin {id:2}: expires in {} day(s)", days.unwrap_or(0));
+    }
+    Ok(())
+}
+
+/// Ids of the pins that expired and of those that expire within
+/// `EXPIRY_REMINDER` days, each soonest first
+fn expiring_ids(pins: &Pins, now: u64) -> (Vec<u8>, Vec<u8>) {
+    let mut ids: Vec<_> = pins
+        .iter_ids()
+        .filter_map(|id| Some((pins.expires(id)?, id)))
+        .collect();
+    ids.sort();
+    let mut expired = Vec::new();
+    let mut expiring = Vec::new();
+    for (expires, id) in ids {
+        match expiry_days(expires, now) {
+            None => expired.push(id),
+            Some(days) if days <= EXPIRY_REMINDER => expiring.push(id),
+            Some(_) => (),
+        }
+    }
+    (expired, expiring)
+}
+
+/// Whole days, rounded up, until a pin expiring at `expires` expires, or
+/// `None` if it already has
+fn expiry_days(expires: u64, now: u64) -> Option<u64> {
+    (expires > now).then(|| (expires - now).div_ceil(DAY_SECS))
+}
+
+/// The current time in Unix seconds
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+/// Prints the ids of the weak pins, the reused ones that are not weak and
+/// the rest, followed by the expired and soon expiring ones
+///
+/// Empty pins and text secrets are left out of the first three groups.
+fn audit_groups(pins: &Pins, json: bool) -> Result<()> {
+    let reused = pins.reused_ids();
+    let (expired, expiring) = expiring_ids(pins, unix_now());
+    let mut groups: [(&str, Vec<u8>); 5] = [
+        ("weak", vec![]),
+        ("reused", vec![]),
+        ("ok", vec![]),
+        ("expired", expired),
+        ("expiring", expiring),
+    ];
+    for pin in pins.iter() {
+        if pin.pin == 0 || pins.kind(pin.id) != PinKind::Numeric {
+            continue;
+        }
+        let group = if pins::is_weak(pin.pin) {
+            0
+        } else if reused.contains(&pin.id) {
+            1
+        } else {
+            2
+        };
+        groups[group].1.push(pin.id);
+    }
+    if json {
+        let groups: Vec<_> = groups
+            .iter()
+            .map(|(name, ids)| {
+                let ids: Vec<_> = ids.iter().map(u8::to_string).collect();
+                format!(
+                    r#""{name}":{{"count":{},"ids":[{}]}}"#,
+                    ids.len(),
+                    ids.join(",")
+                )
+            })
+            .collect();
+        println!("{{{}}}", groups.join(","));
+        return Ok(());
+    }
+    for (name, ids) in &groups {
+        println!("{name} ({}): {}", ids.len(), id_ranges(ids));
+    }
+    Ok(())
+}
+
+/// Prints the key that `master` XORs each pin id with and flags keys that
+/// repeat
+fn analyze_master(master: u32) -> Result<()> {
+    println!("Master {master}");
+    if master == 0 {
+        println!("xorshift32 never leaves 0, so every key is 0 and pins are stored as they are");
+    } else {
+        println!("xorshift32 repeats only after 2^32 - 1 steps for any master other than 0");
+    }
+    let keys: Vec<_> = (0..=99).map(|id| pins::n_shift(master, id + 1)).collect();
+    for (id, key) in keys.iter().enumerate() {
+        println!("Pin {id:2}: {key:#010x}");
+    }
+    let repeated: Vec<_> = (1..=99u8)
+        .filter(|&id| keys[..id as usize].contains(&keys[id as usize]))
+        .collect();
+    if repeated.is_empty() {
+        println!("Repeated keys: none");
+    } else {
+        println!("Ids reusing an earlier key: {}", id_ranges(&repeated));
+    }
+    Ok(())
+}
+
+/// Prints the version and build of vcry, for bug reports
+fn version() -> Result<()> {
+    println!("vcry {}", env!("CARGO_PKG_VERSION"));
+    println!("Commit: {}", env!("VCRY_GIT_COMMIT"));
+    println!("Built: {}", env!("VCRY_BUILD_DATE"));
+    let features: Vec<_> = [("tui", cfg!(feature = "tui"))]
+        .into_iter()
+        .filter_map(|(name, enabled)| enabled.then_some(name))
+        .collect();
+    println!("Features: {}", features.join(", "));
+    Ok(())
+}
+
+/// Prints the stored form of a single pin in hex, as it appears in a vault
+fn encrypt_pin(master: u32, id: u8, pin: u32) -> Result<()> {
+    if id > 99 {
+        bail!("Id is too large: {id} > 99");
+    }
+    if pin > pins::MAX_PIN {
+        bail!("Pin is too large: {pin} > {}", pins::MAX_PIN);
+    }
+    let raw = if DETERMINISTIC.load(Ordering::Relaxed) {
+        pins::encrypt(master, id, pin, &mut StepRng::new(0, 0))?
+    } else {
+        pins::encrypt(master, id, pin, &mut rand::rngs::OsRng)?
+    };
+    println!("{raw:08x}");
+    Ok(())
+}
+
+/// Prints the pin that the stored form `raw` decrypts to
+fn decrypt_pin(master: u32, id: u8, raw: &str) -> Result<()> {
+    if id > 99 {
+        bail!("Id is too large: {id} > 99");
+    }
+    let digits = raw.strip_prefix("0x").unwrap_or(raw);
+    let raw = u32::from_str_radix(digits, 16)
+        .with_context(|| format!("Not a 32 bit hex number: {raw}"))?;
+    println!("{}", pins::decrypt(master, id, raw));
+    Ok(())
+}
+
+/// Distributes the pins round-robin across `count` new vaults named
+/// `<file>.1` to `<file>.<count>`, renumbering them in each
+///
+/// Text secrets, histories, expiry and last use times go along with their
+/// pins, and every new vault gets the metadata. Duress vaults are refused,
+/// since their decoy section cannot be split.
+fn split(file: &str, master: u32, count: usize) -> Result<()> {
+    if count == 0 {
+        bail!("Cannot split into 0 vaults");
+    }
+    let pins = load(file, master)?;
+    if pins.has_duress() {
+        bail!("Cannot split a vault with a duress master");
+    }
+    let paths: Vec<_> = (1..=count).map(|i| format!("{file}.{i}")).collect();
+    for path in &paths {
+        if std::path::Path::new(path).exists() {
+            bail!("Refusing to overwrite {path}");
+        }
+    }
+    let mut parts: Vec<_> = (0..count)
+        .map(|_| {
+            let mut part = Pins::new(master);
+            part.set_metadata(pins.metadata());
+            part
+        })
+        .collect();
+    for (i, pin) in pins.iter().enumerate() {
+        let part = &mut parts[i % count];
+        part.add_copy(&pins, i);
+        let new_id = part.get(part.len() - 1).id;
+        println!("Pin {:2} -> {} as pin {new_id}", pin.id, paths[i % count]);
+    }
+    let mut outputs = Vec::new();
+    for part in &parts {
+        outputs.push(save(part)?);
+    }
+    if DRY_RUN.load(Ordering::Relaxed) {
+        eprintln!(">> Dry run, no vaults were written.");
+        return Ok(());
+    }
+    for (bytes, path) in outputs.iter().zip(&paths) {
+        file::write_atomic(path, bytes)?;
+    }
+    Ok(())
+}
+
+/// Renumbers the pins of a vault from 0, listing the ids that change
+fn compact(file: &str, master: u32, yes: bool) -> Result<()> {
+    let mut pins = load(file, master)?;
+    let moved = pins.compact_ids();
+    if moved.is_empty() {
+        println!("Ids are already compact");
+        return Ok(());
+    }
+    for (old_id, new_id) in &moved {
+        println!("Pin {old_id:2} -> pin {new_id}");
+    }
+    if DRY_RUN.load(Ordering::Relaxed) {
+        eprintln!(">> Dry run, {file} was not written.");
+        return Ok(());
+    }
+    if !yes {
+        if !io::stdin().is_terminal() {
+            bail!(
+                "Compacting changes the ids of {} pin(s), pass --yes to do it anyway",
+                moved.len()
+            );
+        }
+        confirm(&format!("This changes the ids of {} pin(s).", moved.len()))?;
+    }
+    write_vault(file, &save(&pins)?)
+}
+
+fn load(file: &str, master: u32) -> Result<Pins> {
+    let bytes = read_file("vault file", file)?;
+    let pins = timed("load", || Pins::load_checked(&bytes, master))?;
+    if STRICT.load(Ordering::Relaxed) {
+        check_health(&pins).with_context(|| format!("{file} fails --strict"))?;
+    }
+    Ok(pins)
+}
+
+/// Fails naming every weak, reused or expired pin, and whether fewer than
+/// `CAPACITY_WARNING` ids are left, which the TUI only warns about
+fn check_health(pins: &Pins) -> Result<()> {
+    let mut problems = Vec::new();
+    for pin in pins.iter().filter(|pin| pin.pin != 0) {
+        let kind = pins::classify(pin.pin);
+        if kind != WeakKind::Ok {
+            problems.push(format!("pin {} is weak: {kind}", pin.id));
+        }
+    }
+    for id in pins.reused_ids() {
+        problems.push(format!("pin {id} is reused"));
+    }
+    let (expired, _) = expiring_ids(pins, unix_now());
+    for id in expired {
+        problems.push(format!("pin {id} expired"));
+    }
+    let remaining = pins.remaining();
+    if remaining <= CAPACITY_WARNING {
+        problems.push(format!("only {remaining} id(s) left"));
+    }
+    if !problems.is_empty() {
+        bail!("{}", problems.join(", "));
     }
     Ok(())
 }
@@ -0,0 +1,163 @@
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::{
+    prelude::*,
+    symbols::border,
+    widgets::{block::*, *},
+};
+use vault_crypt::{pins::MAX_PIN, re::Cracker};
+
+use crate::tui;
+
+/// Narrows down candidate masters from a previous crack by checking them
+/// against pins the user remembers
+pub struct MatchApp {
+    cracker: Cracker,
+    candidates: Vec<u32>,
+    /// Remembered `(id, pin)` pairs
+    known: Vec<(u8, u32)>,
+    /// Candidates that decrypt every known pair correctly
+    matching: Vec<u32>,
+    input: String,
+    state: ListState,
+    exit: bool,
+}
+
+impl MatchApp {
+    pub fn new(cracker: Cracker, candidates: Vec<u32>) -> Self {
+        Self {
+            cracker,
+            matching: candidates.clone(),
+            candidates,
+            known: Vec::new(),
+            input: String::new(),
+            state: ListState::default(),
+            exit: false,
+        }
+    }
+
+    pub fn run(&mut self, terminal: &mut tui::Tui) -> Result<()> {
+        while !self.exit {
+            terminal.draw(|frame| frame.render_widget(&mut *self, frame.size()))?;
+            self.handle_events()?;
+        }
+        Ok(())
+    }
+
+    fn handle_events(&mut self) -> Result<()> {
+        match event::read()? {
+            Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
+                self.handle_key_event(key_event);
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn handle_key_event(&mut self, key_event: KeyEvent) {
+        match key_event.code {
+            KeyCode::Char(c @ ('0'..='9' | '=')) if self.input.len() < 12 => {
+                self.input.push(c);
+            }
+            KeyCode::Backspace if !self.input.is_empty() => {
+                self.input.pop();
+            }
+            KeyCode::Backspace if self.input.is_empty() && !self.known.is_empty() => {
+                self.known.pop();
+                self.refilter();
+            }
+            KeyCode::Enter => 'blk: {
+                let Some(pair) = parse_known(&self.input) else {
+                    break 'blk;
+                };
+                self.known.retain(|&(id, _)| id != pair.0);
+                self.known.push(pair);
+                self.input.clear();
+                self.refilter();
+            }
+            KeyCode::Up => self.state.select(Some(
+                self.state.selected().map_or(0, |i| i.saturating_sub(1)),
+            )),
+            KeyCode::Down if !self.matching.is_empty() => self.state.select(Some(
+                self.state
+                    .selected()
+                    .map_or(0, |i| (i + 1).min(self.matching.len() - 1)),
+            )),
+            KeyCode::Esc | KeyCode::Char('q') => self.exit = true,
+            _ => (),
+        }
+    }
+
+    fn refilter(&mut self) {
+        self.matching = self
+            .candidates
+            .iter()
+            .copied()
+            .filter(|&master| self.cracker.matches(master, &self.known))
+            .collect();
+        self.state.select(None);
+    }
+}
+
+/// Parses a remembered pin entered as `id=pin`
+fn parse_known(input: &str) -> Option<(u8, u32)> {
+    let (id, pin) = input.split_once('=')?;
+    let id: u8 = id.parse().ok()?;
+    let pin: u32 = pin.parse().ok()?;
+    (id <= 99 && pin <= MAX_PIN).then_some((id, pin))
+}
+
+impl Widget for &mut MatchApp {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let title = Title::from(
+            format!(
+                "Matching masters: {} of {}",
+                self.matching.len(),
+                self.candidates.len()
+            )
+            .bold(),
+        );
+        let instructions = Title::from(Line::from(vec![
+            " Add pin".into(),
+            "<Enter>".blue().bold(),
+            " Remove last".into(),
+            "<Backspace>".blue().bold(),
+            " Quit".into(),
+            "<Q>".blue().bold(),
+        ]));
+        let block = Block::default()
+            .title(title.alignment(Alignment::Center))
+            .title(
+                instructions
+                    .alignment(Alignment::Center)
+                    .position(Position::Bottom),
+            )
+            .borders(Borders::ALL)
+            .border_set(border::THICK);
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let [known_area, input_area, list_area] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Min(0),
+        ])
+        .areas(inner);
+        let known: Vec<_> = self
+            .known
+            .iter()
+            .map(|(id, pin)| format!("{id}={pin}"))
+            .collect();
+        Paragraph::new(format!("Known pins: {}", known.join(", "))).render(known_area, buf);
+        Paragraph::new(format!("Add (id=pin): {}_", self.input)).render(input_area, buf);
+        StatefulWidget::render(
+            List::new(self.matching.iter().map(|master| format!("{master:9}")))
+                .style(Style::default().green())
+                .highlight_symbol(">>")
+                .direction(ListDirection::TopToBottom),
+            list_area,
+            buf,
+            &mut self.state,
+        );
+    }
+}
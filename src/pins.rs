@@ -1,74 +1,421 @@
-use std::collections::HashSet;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    hash::{Hash, Hasher},
+    ops::RangeBounds,
+};
 
-use anyhow::{bail, Result};
-use rand::{rngs::OsRng, Rng};
+use anyhow::{bail, Context, Result};
+use rand::{rngs::OsRng, Rng, RngCore};
+
+pub use crate::format::{
+    VERSION_DURESS, VERSION_EXPIRY, VERSION_HISTORY, VERSION_LEGACY, VERSION_METADATA,
+    VERSION_TEXTS, VERSION_USAGE,
+};
+use crate::{
+    error::VaultError,
+    format::{
+        self, Record, Section, VaultFile, RECORD_SIZE, SECTION_VERSION, TIMESTAMP_SIZE,
+        VERSION_MARKER,
+    },
+};
+
+/// Largest value a pin can hold, i.e. nine decimal digits
+pub const MAX_PIN: u32 = 999_999_999;
+
+/// How many previous values are kept per pin
+pub const HISTORY_LEN: usize = 5;
+
+/// Longest text secret in bytes
+pub const MAX_TEXT_LEN: usize = 64;
+
+/// Mixed into a master before deriving its section tag, so the tag is not
+/// part of any keystream
+const SECTION_TAG_SALT: u32 = 0x5643_5259;
+/// Mixed into a master before deriving the history keystream, so it does not
+/// follow the one used for the metadata
+const HISTORY_SALT: u32 = 0x4849_5354;
+/// Mixed into a master before deriving the text secret keystream
+const TEXT_SALT: u32 = 0x5445_5854;
+/// Mixed into a master before deriving the expiry keystream
+const EXPIRY_SALT: u32 = 0x4558_5052;
+/// Mixed into a master before deriving the last use keystream
+const USAGE_SALT: u32 = 0x5553_4544;
 
 #[derive(Default)]
 pub struct Pins {
     master: u32,
     pins: Vec<Pin>,
+    /// Largest id of any pin, or 0 without pins
+    ///
+    /// New pins get the id after it, so it is lowered again when the pin
+    /// holding it is removed.
     max_id: u8,
+    /// Free text describing the vault, stored encrypted
+    metadata: String,
+    /// Whether the metadata or text secrets did not decrypt to valid text,
+    /// which happens with a wrong master or a corrupted vault
+    lossy: bool,
+    /// Previous values of the pins by id, oldest first
+    history: BTreeMap<u8, Vec<u32>>,
+    /// Text secrets by id
+    ///
+    /// Their entries in `pins` are kept empty, so that they are listed along
+    /// with the numeric pins but never written as one.
+    texts: BTreeMap<u8, String>,
+    /// When pins are due to be changed by id, in Unix seconds
+    expiry: BTreeMap<u8, u64>,
+    /// When pins were last copied by id, in Unix seconds
+    last_used: BTreeMap<u8, u64>,
+    /// The section of a duress vault that this master does not open
+    other: Option<OtherSection>,
+}
+
+/// What an entry of a vault holds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PinKind {
+    /// A pin of up to nine digits
+    Numeric,
+    /// A short string, see `MAX_TEXT_LEN`
+    Text,
+}
+
+/// A section of a duress vault that belongs to the other master, which is
+/// written back untouched
+struct OtherSection {
+    /// Index of the section the pins themselves were loaded from
+    index: usize,
+    tag: u8,
+    body: Vec<u8>,
 }
 
 impl Pins {
-    pub fn verify(bytes: &[u8]) -> Result<()> {
-        if bytes.is_empty() {
-            bail!("Input is empty");
-        }
-        let len = bytes[0] as usize;
-        let bytes = &bytes[1..];
-        if bytes.len() < len * 5 {
-            bail!("Not enough bytes for given length");
-        }
-        let mut pins = HashSet::new();
-        for i in 0..len {
-            let id = bytes[i * 5];
-            if id > 99 {
-                bail!("Id is too large: {id} > 99");
-            }
-            if !pins.insert(id) {
-                bail!("Duplicate id: {id}");
-            }
+    /// Creates an empty vault encrypted under `master`
+    pub fn new(master: u32) -> Self {
+        Self {
+            master,
+            ..Self::default()
         }
+    }
+
+    /// Checks that `bytes` have the structure of a vault, down to the last
+    /// byte
+    ///
+    /// See `VaultFile::parse` for the errors.
+    pub fn verify(bytes: &[u8]) -> Result<()> {
+        VaultFile::parse(bytes)?;
         Ok(())
     }
 
+    /// Decrypts a verified vault
+    ///
+    /// Of a duress vault, this loads the section whose tag matches `master`,
+    /// or the first one if neither does.
     pub fn load(bytes: &[u8], master: u32) -> Self {
-        assert!(!bytes.is_empty());
-        let len = bytes[0] as usize;
-        let bytes = &bytes[1..];
-        assert!(bytes.len() >= len * 5);
+        let file = VaultFile::parse(bytes).expect("Vault should be verified");
+        if file.version != VERSION_DURESS {
+            return Self::load_section(&file.sections[0], master);
+        }
+        let index = section_index(&file, master);
+        let mut pins = Self::load_section(&file.sections[index], master);
+        let other = &file.sections[1 - index];
+        pins.other = Some(OtherSection {
+            index,
+            tag: other.tag.expect("Duress sections should be tagged"),
+            body: other.bytes.to_vec(),
+        });
+        pins
+    }
+
+    /// Decrypts the pins and blocks of a parsed section
+    fn load_section(section: &Section, master: u32) -> Self {
         let mut pins = Vec::new();
         let mut max_id = 0;
-        for i in 0..len {
-            let id = bytes[i * 5];
-            assert!(id <= 99);
+        for record in &section.records {
+            max_id = max_id.max(record.id);
+            pins.push(Pin::new(record.id, decrypt(master, record.id, record.pin)));
+        }
+        let mut metadata = String::new();
+        let mut lossy = false;
+        let mut history = BTreeMap::<u8, Vec<u32>>::new();
+        let mut texts = BTreeMap::new();
+        if let Some(bytes) = section.metadata {
+            let mut metadata_bytes = bytes.to_vec();
+            apply_keystream(master, &mut metadata_bytes);
+            metadata = String::from_utf8(metadata_bytes).unwrap_or_else(|err| {
+                lossy = true;
+                String::from_utf8_lossy(err.as_bytes()).into_owned()
+            });
+        }
+        if let Some(bytes) = section.texts {
+            let mut texts_bytes = bytes.to_vec();
+            apply_text_keystream(master, &mut texts_bytes);
+            texts = parse_texts(&texts_bytes, &pins).unwrap_or_else(|| {
+                lossy = true;
+                BTreeMap::new()
+            });
+        }
+        // Before reading the history, which text secrets keep from when they
+        // were numeric pins
+        for &id in texts.keys() {
             max_id = max_id.max(id);
-            let pin_bytes = &bytes[i * 5 + 1..];
-            let pin = (pin_bytes[0] as u32) << 24
-                | (pin_bytes[1] as u32) << 16
-                | (pin_bytes[2] as u32) << 8
-                | pin_bytes[3] as u32;
-            let pin = decrypt(master, id, pin);
-            pins.push(Pin::new(id, pin));
+            pins.push(Pin::new(id, 0));
+        }
+        if let Some(bytes) = section.history {
+            let mut history_bytes = bytes.to_vec();
+            apply_history_keystream(master, &mut history_bytes);
+            for entry in history_bytes.chunks_exact(RECORD_SIZE) {
+                let Record { id, pin: value } = Record::read(entry);
+                // Entries of pins that do not exist only come from a wrong master
+                if pins.iter().any(|pin| pin.id == id) {
+                    history.entry(id).or_default().push(value);
+                }
+            }
+        }
+        let mut expiry = BTreeMap::new();
+        if let Some(bytes) = section.expiry {
+            let mut expiry_bytes = bytes.to_vec();
+            apply_expiry_keystream(master, &mut expiry_bytes);
+            expiry = parse_timestamps(&expiry_bytes, &pins);
+        }
+        let mut last_used = BTreeMap::new();
+        if let Some(bytes) = section.last_used {
+            let mut last_used_bytes = bytes.to_vec();
+            apply_usage_keystream(master, &mut last_used_bytes);
+            last_used = parse_timestamps(&last_used_bytes, &pins);
         }
         pins.sort_by_key(|pin| pin.id);
         Self {
             master,
             pins,
             max_id,
+            metadata,
+            lossy,
+            history,
+            texts,
+            expiry,
+            last_used,
+            other: None,
+        }
+    }
+
+    /// Like `load`, but fails with `VaultError::WrongMaster` if a pin decrypts
+    /// to a value that no pin can hold or the metadata or text secrets do not
+    /// decrypt to text
+    pub fn load_checked(bytes: &[u8], master: u32) -> Result<Self> {
+        let file = VaultFile::parse(bytes)?;
+        if file.version == VERSION_DURESS
+            && !file
+                .sections
+                .iter()
+                .any(|section| section.tag == Some(section_tag(master)))
+        {
+            bail!(VaultError::WrongMaster);
         }
+        let pins = Self::load(bytes, master);
+        if pins.lossy || pins.iter().any(|pin| pin.pin > MAX_PIN) {
+            bail!(VaultError::WrongMaster);
+        }
+        Ok(pins)
     }
 
-    pub fn save(&self) -> Vec<u8> {
+    /// Checks that the in-memory pins could be written to a valid file
+    pub fn validate(&self) -> Result<()> {
+        let mut last_id = None;
+        for pin in self.iter() {
+            pin.check()?;
+            match last_id {
+                Some(last_id) if last_id == pin.id => bail!("Duplicate id: {}", pin.id),
+                Some(last_id) if last_id > pin.id => {
+                    bail!("Ids are not sorted: {} after {last_id}", pin.id)
+                }
+                _ => (),
+            }
+            last_id = Some(pin.id);
+        }
+        if self.max_id != last_id.unwrap_or(0) {
+            bail!(
+                "Largest id is out of date: {} instead of {}",
+                self.max_id,
+                last_id.unwrap_or(0)
+            );
+        }
+        for (id, text) in &self.texts {
+            if text.len() > MAX_TEXT_LEN {
+                bail!(
+                    "Text secret {id} is too long: {} > {MAX_TEXT_LEN} bytes",
+                    text.len()
+                );
+            }
+        }
+        if self.metadata.len() > u16::MAX as usize {
+            bail!(
+                "Metadata is too long: {} > {} bytes",
+                self.metadata.len(),
+                u16::MAX
+            );
+        }
+        Ok(())
+    }
+
+    pub fn save(&self) -> Result<Vec<u8>> {
+        self.save_with(&mut OsRng)
+    }
+
+    /// Saves the pins, drawing the obfuscation bits from `rng`
+    pub fn save_with(&self, rng: &mut impl RngCore) -> Result<Vec<u8>> {
+        self.validate()?;
         let mut out = Vec::new();
-        out.push(self.len() as u8);
-        for pin in self.pins.iter().filter(|pin| pin.pin != 0) {
+        let Some(other) = &self.other else {
+            // Vaults without metadata, history, texts, expiry or last use
+            // times stay readable by older versions
+            let version = if !self.last_used.is_empty() {
+                VERSION_USAGE
+            } else if !self.expiry.is_empty() {
+                VERSION_EXPIRY
+            } else if !self.texts.is_empty() {
+                VERSION_TEXTS
+            } else if !self.history.is_empty() {
+                VERSION_HISTORY
+            } else if !self.metadata.is_empty() {
+                VERSION_METADATA
+            } else {
+                VERSION_LEGACY
+            };
+            if version != VERSION_LEGACY {
+                out.extend([VERSION_MARKER, version]);
+            }
+            self.save_body(&mut out, version, rng)?;
+            return Ok(out);
+        };
+        let mut body = Vec::new();
+        self.save_body(&mut body, SECTION_VERSION, rng)?;
+        let mut sections = [
+            (section_tag(self.master), body.as_slice()),
+            (other.tag, other.body.as_slice()),
+        ];
+        if other.index == 1 {
+            sections.swap(0, 1);
+        }
+        out.extend([VERSION_MARKER, VERSION_DURESS]);
+        for (tag, body) in sections {
+            out.push(tag);
+            out.extend((body.len() as u32).to_be_bytes());
+            out.extend(body);
+        }
+        Ok(out)
+    }
+
+    /// Writes the pins starting at the pin count, followed by the blocks that
+    /// `version` has
+    ///
+    /// The history, text, expiry and last use blocks are left out when there
+    /// is nothing to put in them and no block follows.
+    fn save_body(&self, out: &mut Vec<u8>, version: u8, rng: &mut impl RngCore) -> Result<()> {
+        // Empty pins are not written, so they must not be counted either
+        let written: Vec<_> = self.iter().filter(|pin| pin.pin != 0).collect();
+        out.push(written.len() as u8);
+        for pin in written {
             out.push(pin.id);
-            let pin = encrypt(self.master, pin.id, pin.pin);
+            let pin = encrypt(self.master, pin.id, pin.pin, rng)?;
             out.extend(pin.to_be_bytes());
         }
-        out
+        if version >= VERSION_METADATA {
+            let mut metadata = self.metadata.clone().into_bytes();
+            apply_keystream(self.master, &mut metadata);
+            out.extend((metadata.len() as u16).to_be_bytes());
+            out.extend(metadata);
+        }
+        let last_used = version >= VERSION_USAGE && !self.last_used.is_empty();
+        let expiry = version >= VERSION_EXPIRY && (!self.expiry.is_empty() || last_used);
+        let texts = version >= VERSION_TEXTS && (!self.texts.is_empty() || expiry);
+        if version >= VERSION_HISTORY && (!self.history.is_empty() || texts) {
+            let mut history = Vec::new();
+            for (&id, values) in &self.history {
+                for value in values {
+                    history.push(id);
+                    history.extend(value.to_be_bytes());
+                }
+            }
+            apply_history_keystream(self.master, &mut history);
+            out.extend((history.len() as u16).to_be_bytes());
+            out.extend(history);
+        }
+        if texts {
+            let mut texts = Vec::new();
+            for (&id, text) in &self.texts {
+                texts.push(id);
+                texts.push(text.len() as u8);
+                texts.extend(text.as_bytes());
+            }
+            apply_text_keystream(self.master, &mut texts);
+            out.extend((texts.len() as u16).to_be_bytes());
+            out.extend(texts);
+        }
+        if expiry {
+            let mut expiry = timestamp_bytes(&self.expiry);
+            apply_expiry_keystream(self.master, &mut expiry);
+            out.extend((expiry.len() as u16).to_be_bytes());
+            out.extend(expiry);
+        }
+        if last_used {
+            let mut last_used = timestamp_bytes(&self.last_used);
+            apply_usage_keystream(self.master, &mut last_used);
+            out.extend((last_used.len() as u16).to_be_bytes());
+            out.extend(last_used);
+        }
+        Ok(())
+    }
+
+    /// Adds an empty set of decoy pins that `duress_master` opens instead of
+    /// the real ones
+    ///
+    /// This is deterrence, not a hidden volume. Anyone holding the file can
+    /// tell from its format version that it has two sections, and each
+    /// section carries a one byte tag of its master so that the right one can
+    /// be opened. The tag narrows a brute force down by a factor of 256, and
+    /// the pins behind either master are as easy to crack as in any other
+    /// vault. What it offers is a believable set of pins to hand over when
+    /// forced to unlock the vault.
+    pub fn add_duress(&mut self, duress_master: u32) -> Result<()> {
+        if self.other.is_some() {
+            bail!("Vault already has a duress master");
+        }
+        if section_tag(duress_master) == section_tag(self.master) {
+            bail!("Duress master cannot be told apart from the master, pick another one");
+        }
+        let mut body = Vec::new();
+        Self::new(duress_master).save_body(&mut body, SECTION_VERSION, &mut OsRng)?;
+        // Which section is real should not follow from their order
+        let mut index = [0];
+        OsRng
+            .try_fill_bytes(&mut index)
+            .context("Random number generator is unavailable")?;
+        self.other = Some(OtherSection {
+            index: (index[0] & 1) as usize,
+            tag: section_tag(duress_master),
+            body,
+        });
+        Ok(())
+    }
+
+    /// Whether the vault has a second section opened by another master
+    pub fn has_duress(&self) -> bool {
+        self.other.is_some()
+    }
+
+    /// The master the pins are encrypted with
+    pub fn master(&self) -> u32 {
+        self.master
+    }
+
+    pub fn metadata(&self) -> &str {
+        &self.metadata
+    }
+
+    pub fn set_metadata(&mut self, metadata: impl Into<String>) {
+        self.metadata = metadata.into();
+        self.lossy = false;
     }
 
     pub fn is_empty(&self) -> bool {
@@ -83,56 +430,573 @@ impl Pins {
         self.pins[index]
     }
 
+    /// Changes a pin, remembering its old value in the pin's history
+    ///
+    /// A text secret becomes a numeric pin.
     pub fn set(&mut self, index: usize, value: u32) {
+        self.texts.remove(&self.pins[index].id);
+        let pin = self.pins[index];
+        if pin.pin != 0 && pin.pin != value {
+            let history = self.history.entry(pin.id).or_default();
+            history.push(pin.pin);
+            if history.len() > HISTORY_LEN {
+                history.remove(0);
+            }
+        }
+        self.amend(index, value);
+    }
+
+    /// Changes a pin without touching its history, for edits that are made
+    /// a digit at a time
+    pub fn amend(&mut self, index: usize, value: u32) {
         self.pins[index].pin = value;
     }
 
+    /// Previous values of the pin with `id`, oldest first
+    pub fn history(&self, id: u8) -> &[u32] {
+        self.history.get(&id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Turns an entry into a text secret holding `text`
+    ///
+    /// The numeric value is dropped, its history is kept.
+    pub fn set_text(&mut self, index: usize, text: impl Into<String>) {
+        self.pins[index].pin = 0;
+        self.texts.insert(self.pins[index].id, text.into());
+    }
+
+    /// The text secret with `id`, if that entry is one
+    pub fn text(&self, id: u8) -> Option<&str> {
+        self.texts.get(&id).map(String::as_str)
+    }
+
+    /// When the pin with `id` is due to be changed, in Unix seconds
+    pub fn expires(&self, id: u8) -> Option<u64> {
+        self.expiry.get(&id).copied()
+    }
+
+    /// Sets or clears when a pin is due to be changed, in Unix seconds
+    ///
+    /// Changing the pin leaves this as it is, so a rotated pin needs a new
+    /// expiry time.
+    pub fn set_expiry(&mut self, index: usize, expires: Option<u64>) {
+        let id = self.pins[index].id;
+        match expires {
+            Some(expires) => self.expiry.insert(id, expires),
+            None => self.expiry.remove(&id),
+        };
+    }
+
+    /// When the pin with `id` was last copied, in Unix seconds
+    pub fn last_used(&self, id: u8) -> Option<u64> {
+        self.last_used.get(&id).copied()
+    }
+
+    /// Records that a pin was copied at `used`, in Unix seconds
+    pub fn mark_used(&mut self, index: usize, used: u64) {
+        self.last_used.insert(self.pins[index].id, used);
+    }
+
+    pub fn kind(&self, id: u8) -> PinKind {
+        if self.texts.contains_key(&id) {
+            PinKind::Text
+        } else {
+            PinKind::Numeric
+        }
+    }
+
     pub fn remove(&mut self, index: usize) {
-        self.pins.remove(index);
+        let pin = self.pins.remove(index);
+        self.history.remove(&pin.id);
+        self.texts.remove(&pin.id);
+        self.expiry.remove(&pin.id);
+        self.last_used.remove(&pin.id);
+        if pin.id == self.max_id {
+            self.max_id = self.iter_ids().max().unwrap_or(0);
+        }
+        self.debug_check_max_id();
     }
 
     pub fn add(&mut self, pin: u32) -> bool {
-        if self.max_id >= 99 {
+        if self.is_full() {
             return false;
         }
         if !self.pins.is_empty() {
             self.max_id += 1;
         }
         self.pins.push(Pin::new(self.max_id, pin));
+        self.debug_check_max_id();
+        true
+    }
+
+    /// How many more pins `add` can add
+    ///
+    /// New pins always get the id after the largest one, so this can be
+    /// fewer than `free_ids` until the ids are compacted.
+    pub fn remaining(&self) -> usize {
+        if self.pins.is_empty() {
+            100
+        } else {
+            99 - self.max_id as usize
+        }
+    }
+
+    /// Whether `add` has no id left to give a new pin
+    pub fn is_full(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    /// Adds a text secret, returning whether there was room for it
+    pub fn add_text(&mut self, text: impl Into<String>) -> bool {
+        if !self.add(0) {
+            return false;
+        }
+        self.set_text(self.len() - 1, text);
         true
     }
 
-    pub fn iter(&self) -> std::slice::Iter<Pin> {
+    /// Adds a copy of the entry of `other` at `index`, with its text, history,
+    /// expiry and last use time, returning whether there was room for it
+    pub fn add_copy(&mut self, other: &Pins, index: usize) -> bool {
+        let pin = other.get(index);
+        if !self.add(pin.pin) {
+            return false;
+        }
+        let id = self.max_id;
+        if let Some(text) = other.texts.get(&pin.id) {
+            self.texts.insert(id, text.clone());
+        }
+        if let Some(history) = other.history.get(&pin.id) {
+            self.history.insert(id, history.clone());
+        }
+        if let Some(&expires) = other.expiry.get(&pin.id) {
+            self.expiry.insert(id, expires);
+        }
+        if let Some(&used) = other.last_used.get(&pin.id) {
+            self.last_used.insert(id, used);
+        }
+        true
+    }
+
+    /// Renumbers the pins to 0 and up in their current order, returning the
+    /// old and new id of every pin that moved
+    ///
+    /// Histories, text secrets, expiry and last use times move along with
+    /// their pins.
+    pub fn compact_ids(&mut self) -> Vec<(u8, u8)> {
+        let mut moved = Vec::new();
+        for (new_id, pin) in (0..).zip(&mut self.pins) {
+            if pin.id == new_id {
+                continue;
+            }
+            moved.push((pin.id, new_id));
+            if let Some(history) = self.history.remove(&pin.id) {
+                self.history.insert(new_id, history);
+            }
+            if let Some(text) = self.texts.remove(&pin.id) {
+                self.texts.insert(new_id, text);
+            }
+            if let Some(expires) = self.expiry.remove(&pin.id) {
+                self.expiry.insert(new_id, expires);
+            }
+            if let Some(used) = self.last_used.remove(&pin.id) {
+                self.last_used.insert(new_id, used);
+            }
+            pin.id = new_id;
+        }
+        self.max_id = self.pins.len().saturating_sub(1) as u8;
+        self.debug_check_max_id();
+        moved
+    }
+
+    /// Swaps the ids of the pins with ids `a` and `b`, along with their places
+    /// in the list, so that a list sorted by id stays sorted
+    ///
+    /// Histories, text secrets, expiry and last use times move along with
+    /// their pins, so each value keeps what belongs to it under its new id.
+    pub fn swap_ids(&mut self, a: u8, b: u8) -> Result<()> {
+        let index = |id| {
+            self.iter_ids()
+                .position(|other| other == id)
+                .with_context(|| format!("No pin with id {id}"))
+        };
+        let (i, j) = (index(a)?, index(b)?);
+        self.pins.swap(i, j);
+        self.pins[i].id = a;
+        self.pins[j].id = b;
+        swap_entries(&mut self.history, a, b);
+        swap_entries(&mut self.texts, a, b);
+        swap_entries(&mut self.expiry, a, b);
+        swap_entries(&mut self.last_used, a, b);
+        Ok(())
+    }
+
+    fn debug_check_max_id(&self) {
+        debug_assert_eq!(self.max_id, self.iter_ids().max().unwrap_or(0));
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Pin> {
         self.pins.iter()
     }
+
+    /// Ids of the pins, in the order the pins are listed
+    pub fn iter_ids(&self) -> impl Iterator<Item = u8> + '_ {
+        self.iter().map(|pin| pin.id)
+    }
+
+    /// Number of pins, including empty ones
+    pub fn count(&self) -> usize {
+        self.len()
+    }
+
+    /// Ids from 0 to 99 that no pin uses, in ascending order
+    pub fn free_ids(&self) -> Vec<u8> {
+        (0..=99)
+            .filter(|id| !self.iter_ids().any(|used| used == *id))
+            .collect()
+    }
+
+    /// Number of pins whose id lies in `ids`
+    pub fn count_in(&self, ids: impl RangeBounds<u8>) -> usize {
+        self.iter_ids().filter(|id| ids.contains(id)).count()
+    }
+
+    /// Number of non-empty pins that are weak
+    pub fn count_weak(&self) -> usize {
+        self.iter()
+            .filter(|pin| pin.pin != 0 && is_weak(pin.pin))
+            .count()
+    }
+
+    /// Number of non-empty pins that reuse the value of another pin
+    pub fn count_duplicates(&self) -> usize {
+        let mut seen = HashSet::new();
+        self.iter()
+            .filter(|pin| pin.pin != 0 && !seen.insert(pin.pin))
+            .count()
+    }
+
+    /// Ids of the non-empty pins that share their value with another pin,
+    /// including the first of them
+    pub fn reused_ids(&self) -> Vec<u8> {
+        let mut counts = HashMap::new();
+        for pin in self.iter().filter(|pin| pin.pin != 0) {
+            *counts.entry(pin.pin).or_insert(0) += 1;
+        }
+        self.iter()
+            .filter(|pin| pin.pin != 0 && counts[&pin.pin] > 1)
+            .map(|pin| pin.id)
+            .collect()
+    }
 }
 
-#[derive(Clone, Copy)]
+/// Pins compare equal when they hold the same decrypted `(id, pin)` pairs,
+/// text secrets, expiry times, last use times and metadata
+///
+/// The master and the order the pins are stored in are ignored, so two vaults
+/// that were encrypted under different masters are equal once they decrypt to
+/// the same content. Histories are ignored as well, since they record how the
+/// pins got their values rather than the values themselves, and so is the
+/// other section of a duress vault.
+impl PartialEq for Pins {
+    fn eq(&self, other: &Self) -> bool {
+        self.sorted() == other.sorted()
+            && self.texts == other.texts
+            && self.expiry == other.expiry
+            && self.last_used == other.last_used
+            && self.metadata == other.metadata
+    }
+}
+
+impl Eq for Pins {}
+
+/// Hashes the same content that `PartialEq` compares
+impl Hash for Pins {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.sorted().hash(state);
+        self.texts.hash(state);
+        self.expiry.hash(state);
+        self.last_used.hash(state);
+        self.metadata.hash(state);
+    }
+}
+
+impl Pins {
+    fn sorted(&self) -> Vec<Pin> {
+        let mut pins = self.pins.clone();
+        pins.sort_by_key(|pin| pin.id);
+        pins
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Pin {
     pub id: u8,
     pub pin: u32,
 }
 
 impl Pin {
+    /// Makes a pin without checking it, see `try_new`
     pub fn new(id: u8, pin: u32) -> Self {
         Self { id, pin }
     }
+
+    /// Makes a pin, failing if the id is above 99 or the pin above `MAX_PIN`
+    pub fn try_new(id: u8, pin: u32) -> Result<Self> {
+        let pin = Self { id, pin };
+        pin.check()?;
+        Ok(pin)
+    }
+
+    /// Checks that the pin could be written to a vault
+    fn check(&self) -> Result<()> {
+        if self.id > 99 {
+            bail!("Id is too large: {} > 99", self.id);
+        }
+        if self.pin > MAX_PIN {
+            bail!("Pin {} is too large: {} > {MAX_PIN}", self.id, self.pin);
+        }
+        Ok(())
+    }
+}
+
+/// Derives the key a pin is encrypted with from the master and its id
+///
+/// Vaults are always written with `XorShift`. Implement this, or pass a
+/// closure, to try another keystream with `encrypt_with` and `decrypt_with`.
+pub trait KeyStream {
+    fn key(&self, master: u32, id: u8) -> u32;
 }
 
-pub fn encrypt(master: u32, id: u8, pin: u32) -> u32 {
-    let pin = encapsulate(pin);
-    n_shift(master, id + 1) ^ pin
+impl<F: Fn(u32, u8) -> u32> KeyStream for F {
+    fn key(&self, master: u32, id: u8) -> u32 {
+        self(master, id)
+    }
+}
+
+/// The keystream of the vault format, the master shifted once more than the
+/// id
+pub struct XorShift;
+
+impl KeyStream for XorShift {
+    fn key(&self, master: u32, id: u8) -> u32 {
+        n_shift(master, id + 1)
+    }
+}
+
+pub fn encrypt(master: u32, id: u8, pin: u32, rng: &mut impl RngCore) -> Result<u32> {
+    encrypt_with(&XorShift, master, id, pin, rng)
 }
 
 pub fn decrypt(master: u32, id: u8, pin: u32) -> u32 {
-    let pin = n_shift(master, id + 1) ^ pin;
+    decrypt_with(&XorShift, master, id, pin)
+}
+
+/// Like `encrypt`, with the key taken from `keystream`
+pub fn encrypt_with(
+    keystream: &impl KeyStream,
+    master: u32,
+    id: u8,
+    pin: u32,
+    rng: &mut impl RngCore,
+) -> Result<u32> {
+    let pin = encapsulate(pin, rng)?;
+    Ok(keystream.key(master, id) ^ pin)
+}
+
+/// Like `decrypt`, with the key taken from `keystream`
+pub fn decrypt_with(keystream: &impl KeyStream, master: u32, id: u8, pin: u32) -> u32 {
+    let pin = keystream.key(master, id) ^ pin;
     decapsulate(pin)
 }
 
-pub fn encapsulate(pin: u32) -> u32 {
+/// Reads the format version of a vault
+pub fn format_version(bytes: &[u8]) -> Result<u8> {
+    Ok(format::split_version(bytes)?.0)
+}
+
+/// Stored form of the pin with `id` in a verified vault, from the section
+/// that `load` would pick for `master`
+pub fn stored_pin(bytes: &[u8], master: u32, id: u8) -> Option<u32> {
+    let file = VaultFile::parse(bytes).ok()?;
+    let section = &file.sections[section_index(&file, master)];
+    let record = section.records.iter().find(|record| record.id == id)?;
+    Some(record.pin)
+}
+
+/// Index of the section whose tag matches `master`, or of the first one if
+/// neither does
+fn section_index(file: &VaultFile, master: u32) -> usize {
+    file.sections
+        .iter()
+        .position(|section| section.tag == Some(section_tag(master)))
+        .unwrap_or(0)
+}
+
+/// One byte derived from a master that tells which section of a duress vault
+/// it opens
+fn section_tag(master: u32) -> u8 {
+    (n_shift(master ^ SECTION_TAG_SALT, 3) >> 24) as u8
+}
+
+/// Encrypts or decrypts `bytes` with the keystream that follows the one used
+/// for the pins, which never shifts further than id 99
+fn apply_keystream(master: u32, bytes: &mut [u8]) {
+    apply_keystream_from(n_shift(master, 101), bytes);
+}
+
+/// Encrypts or decrypts pin history with a keystream of its own
+fn apply_history_keystream(master: u32, bytes: &mut [u8]) {
+    apply_keystream_from(n_shift(master ^ HISTORY_SALT, 1), bytes);
+}
+
+/// Encrypts or decrypts text secrets with a keystream of their own
+fn apply_text_keystream(master: u32, bytes: &mut [u8]) {
+    apply_keystream_from(n_shift(master ^ TEXT_SALT, 1), bytes);
+}
+
+/// Encrypts or decrypts expiry times with a keystream of their own
+fn apply_expiry_keystream(master: u32, bytes: &mut [u8]) {
+    apply_keystream_from(n_shift(master ^ EXPIRY_SALT, 1), bytes);
+}
+
+/// Swaps the entries of `a` and `b`, either of which may be missing
+fn swap_entries<T>(map: &mut BTreeMap<u8, T>, a: u8, b: u8) {
+    let (value_a, value_b) = (map.remove(&a), map.remove(&b));
+    if let Some(value) = value_a {
+        map.insert(b, value);
+    }
+    if let Some(value) = value_b {
+        map.insert(a, value);
+    }
+}
+
+/// Encrypts or decrypts last use times with a keystream of their own
+fn apply_usage_keystream(master: u32, bytes: &mut [u8]) {
+    apply_keystream_from(n_shift(master ^ USAGE_SALT, 1), bytes);
+}
+
+/// Parses decrypted `[id][seconds]` entries, skipping those of ids that no
+/// pin has, which only come from a wrong master
+fn parse_timestamps(bytes: &[u8], pins: &[Pin]) -> BTreeMap<u8, u64> {
+    let mut timestamps = BTreeMap::new();
+    for entry in bytes.chunks_exact(TIMESTAMP_SIZE) {
+        let (id, seconds) = entry.split_at(1);
+        if pins.iter().any(|pin| pin.id == id[0]) {
+            let seconds = u64::from_be_bytes(seconds.try_into().unwrap());
+            timestamps.insert(id[0], seconds);
+        }
+    }
+    timestamps
+}
+
+/// Writes `[id][seconds]` entries, to be encrypted
+fn timestamp_bytes(timestamps: &BTreeMap<u8, u64>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for (&id, seconds) in timestamps {
+        bytes.push(id);
+        bytes.extend(seconds.to_be_bytes());
+    }
+    bytes
+}
+
+/// Parses decrypted `[id][length][text]` entries, returning `None` if they
+/// do not fit together, which only a wrong master or corruption causes
+fn parse_texts(mut bytes: &[u8], pins: &[Pin]) -> Option<BTreeMap<u8, String>> {
+    let mut texts = BTreeMap::new();
+    while let [id, len, rest @ ..] = bytes {
+        let len = *len as usize;
+        if *id > 99 || len > MAX_TEXT_LEN || rest.len() < len {
+            return None;
+        }
+        if pins.iter().any(|pin| pin.id == *id) {
+            return None;
+        }
+        let text = String::from_utf8(rest[..len].to_vec()).ok()?;
+        if texts.insert(*id, text).is_some() {
+            return None;
+        }
+        bytes = &rest[len..];
+    }
+    bytes.is_empty().then_some(texts)
+}
+
+fn apply_keystream_from(mut state: u32, bytes: &mut [u8]) {
+    for chunk in bytes.chunks_mut(4) {
+        for (byte, key) in chunk.iter_mut().zip(state.to_be_bytes()) {
+            *byte ^= key;
+        }
+        state = xorshift32(state);
+    }
+}
+
+/// Fails for a master that leaves the pins unencrypted, unless `allow_weak`
+///
+/// xorshift32 never leaves 0, so every key that master 0 derives is 0 and
+/// its pins are stored as they are.
+pub fn check_master(master: u32, allow_weak: bool) -> Result<()> {
+    if master == 0 && !allow_weak {
+        bail!("Master 0 turns encryption off, every pin would be stored as it is");
+    }
+    Ok(())
+}
+
+/// Generates a random nine digit pin
+pub fn random_pin(rng: &mut impl Rng) -> u32 {
+    rng.gen_range(100_000_000..=MAX_PIN)
+}
+
+/// Rules that generated pins follow
+#[derive(Clone, Copy, Debug)]
+pub struct PinPolicy {
+    /// Exact number of digits
+    length: u8,
+    /// Whether pins that `is_weak` flags are drawn again
+    avoid_weak: bool,
+}
+
+impl PinPolicy {
+    pub fn new(length: u8, avoid_weak: bool) -> Result<Self> {
+        if !(1..=9).contains(&length) {
+            bail!("Pin length must be from 1 to 9 digits, not {length}");
+        }
+        if avoid_weak && length <= 2 {
+            bail!("Pins of {length} digit(s) are always weak");
+        }
+        Ok(Self { length, avoid_weak })
+    }
+}
+
+/// Nine digits that are not weak
+impl Default for PinPolicy {
+    fn default() -> Self {
+        Self {
+            length: 9,
+            avoid_weak: true,
+        }
+    }
+}
+
+/// Generates a random pin that follows `policy`, drawing again until one
+/// does
+pub fn random_pin_with_policy(rng: &mut impl Rng, policy: PinPolicy) -> u32 {
+    let min = 10u32.pow(policy.length as u32 - 1);
+    let max = 10u32.pow(policy.length as u32) - 1;
+    loop {
+        let pin = rng.gen_range(min..=max);
+        if !policy.avoid_weak || !is_weak(pin) {
+            return pin;
+        }
+    }
+}
+
+pub fn encapsulate(pin: u32, rng: &mut impl RngCore) -> Result<u32> {
+    let mut bits = [0];
+    rng.try_fill_bytes(&mut bits)
+        .context("Random number generator is unavailable")?;
     let mut x = pin;
-    x |= OsRng.gen_range(0b00..=0b11) << 30;
-    x
+    x |= (bits[0] as u32 & 0b11) << 30;
+    Ok(x)
 }
 
 pub fn decapsulate(pin: u32) -> u32 {
@@ -156,3 +1020,165 @@ pub fn xorshift32(state: u32) -> u32 {
     x ^= x << 5;
     x
 }
+
+/// Pins that show up at the top of every leaked pin list
+const COMMON_PINS: [u32; 24] = [
+    0, 1004, 1010, 1111, 1122, 1212, 1234, 1313, 2000, 2001, 2222, 2580, 3333, 4321, 4444, 5555,
+    6666, 6969, 7777, 8888, 9999, 123123, 654321, 987654321,
+];
+
+/// Estimates how hard `pin` is to guess, from 0 (trivial) to 4 (strong)
+///
+/// This is a zxcvbn-like heuristic adapted to numeric pins: common pins,
+/// repeated digits and digit sequences score 0, dates and repeated chunks
+/// score 1, everything else is rated by its length.
+pub fn guessability(pin: u32) -> u8 {
+    let digits = digits(pin);
+    if digits.len() <= 2
+        || COMMON_PINS.contains(&pin)
+        || is_repeated_chunk(&digits, 1)
+        || is_sequence(&digits)
+    {
+        return 0;
+    }
+    if is_date(&digits) || is_repeated_chunk(&digits, digits.len() / 2) || distinct(&digits) <= 2 {
+        return 1;
+    }
+    match digits.len() {
+        0..=4 => 2,
+        5..=6 => 3,
+        _ => 4,
+    }
+}
+
+/// Why a pin is weak, as found by `classify`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WeakKind {
+    /// The pin is 0, which also means it is empty
+    AllZeros,
+    /// The pin is one of the most commonly used ones
+    Common,
+    /// The pin has at most two digits
+    Short,
+    /// The pin repeats a digit, or a chunk of up to half its digits
+    Repeated,
+    /// The digits count up or down
+    Sequential,
+    /// The pin reads as a date
+    Date,
+    /// The pin uses at most two different digits
+    FewDigits,
+    /// The pin is not weak
+    Ok,
+}
+
+impl fmt::Display for WeakKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::AllZeros => "all zeros",
+            Self::Common => "common pin",
+            Self::Short => "too short",
+            Self::Repeated => "repeated digits",
+            Self::Sequential => "sequential digits",
+            Self::Date => "looks like a date",
+            Self::FewDigits => "few different digits",
+            Self::Ok => "ok",
+        })
+    }
+}
+
+/// Finds the first reason `pin` is weak, following the same rules as
+/// `guessability`
+pub fn classify(pin: u32) -> WeakKind {
+    let digits = digits(pin);
+    if pin == 0 {
+        WeakKind::AllZeros
+    } else if COMMON_PINS.contains(&pin) {
+        WeakKind::Common
+    } else if digits.len() <= 2 {
+        WeakKind::Short
+    } else if is_repeated_chunk(&digits, digits.len() / 2) {
+        WeakKind::Repeated
+    } else if is_sequence(&digits) {
+        WeakKind::Sequential
+    } else if is_date(&digits) {
+        WeakKind::Date
+    } else if distinct(&digits) <= 2 {
+        WeakKind::FewDigits
+    } else {
+        WeakKind::Ok
+    }
+}
+
+/// Whether `pin` is easy enough to guess that it should be changed
+pub fn is_weak(pin: u32) -> bool {
+    classify(pin) != WeakKind::Ok
+}
+
+/// Whether `pin` reads as a date such as DDMMYYYY or YYYYMMDD
+pub fn looks_like_date(pin: u32) -> bool {
+    is_date(&digits(pin))
+}
+
+fn digits(pin: u32) -> Vec<u8> {
+    pin.to_string().bytes().map(|b| b - b'0').collect()
+}
+
+/// Whether `digits` consists of a chunk of at most `max_chunk` digits repeated
+fn is_repeated_chunk(digits: &[u8], max_chunk: usize) -> bool {
+    (1..=max_chunk.min(digits.len() / 2)).any(|size| {
+        digits.len().is_multiple_of(size)
+            && digits.chunks(size).all(|chunk| chunk == &digits[..size])
+    })
+}
+
+/// Whether `digits` counts up or down, wrapping around from 9 to 0
+fn is_sequence(digits: &[u8]) -> bool {
+    let steps = || digits.windows(2).map(|w| (w[1] + 10 - w[0]) % 10);
+    steps().all(|step| step == 1) || steps().all(|step| step == 9)
+}
+
+fn distinct(digits: &[u8]) -> usize {
+    let mut seen = [false; 10];
+    for &digit in digits {
+        seen[digit as usize] = true;
+    }
+    seen.iter().filter(|&&seen| seen).count()
+}
+
+/// Whether `digits` looks like a date, allowing for a dropped leading zero
+fn is_date(digits: &[u8]) -> bool {
+    let mut padded = digits.to_vec();
+    if !padded.len().is_multiple_of(2) {
+        padded.insert(0, 0);
+    }
+    let number = |range: std::ops::Range<usize>| {
+        padded[range]
+            .iter()
+            .fold(0, |acc, &digit| acc * 10 + digit as u32)
+    };
+    let day = |n: u32| (1..=31).contains(&n);
+    let month = |n: u32| (1..=12).contains(&n);
+    let year = |n: u32| (1900..=2099).contains(&n);
+    match padded.len() {
+        // MMDD, DDMM or YYYY
+        4 => {
+            (month(number(0..2)) && day(number(2..4)))
+                || (day(number(0..2)) && month(number(2..4)))
+                || year(number(0..4))
+        }
+        // DDMMYY, MMDDYY or YYMMDD
+        6 => {
+            (day(number(0..2)) && month(number(2..4)))
+                || (month(number(0..2)) && day(number(2..4)))
+                || (month(number(2..4)) && day(number(4..6)))
+        }
+        // DDMMYYYY, MMDDYYYY or YYYYMMDD
+        8 => {
+            (day(number(0..2)) && month(number(2..4)) && year(number(4..8)))
+                || (month(number(0..2)) && day(number(2..4)) && year(number(4..8)))
+                || (year(number(0..4)) && month(number(4..6)) && day(number(6..8)))
+        }
+        _ => false,
+    }
+}
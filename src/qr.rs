@@ -0,0 +1,235 @@
+//! Minimal QR code encoder for showing a pin in the terminal
+//!
+//! Only version 1 codes with medium error correction in numeric mode are
+//! supported, which holds up to 34 digits and so any pin. The layout follows
+//! ISO/IEC 18004, always using mask pattern 0.
+
+use anyhow::{bail, Result};
+
+/// Modules per side of a version 1 code
+const SIZE: usize = 21;
+/// Codewords of a version 1 code with medium error correction
+const DATA_CODEWORDS: usize = 16;
+const EC_CODEWORDS: usize = 10;
+/// Modules of light border around the code that scanners need
+const QUIET_ZONE: usize = 4;
+
+pub struct QrCode {
+    /// Dark modules, indexed by row and then column
+    modules: [[bool; SIZE]; SIZE],
+}
+
+impl QrCode {
+    /// Encodes a string of decimal digits
+    pub fn encode_numeric(digits: &str) -> Result<Self> {
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            bail!("Only digits can be encoded");
+        }
+        if digits.len() > 34 {
+            bail!("Too many digits to encode: {} > 34", digits.len());
+        }
+        let mut codewords = data_codewords(digits);
+        codewords.extend(reed_solomon_remainder(&codewords));
+        let mut code = Self {
+            modules: [[false; SIZE]; SIZE],
+        };
+        let mut function = [[false; SIZE]; SIZE];
+        code.draw_function_patterns(&mut function);
+        code.draw_codewords(&codewords, &function);
+        Ok(code)
+    }
+
+    /// Renders the code with half block characters, two rows per line
+    ///
+    /// Light modules are drawn as blocks, which suits terminals with a dark
+    /// background.
+    pub fn to_unicode(&self) -> String {
+        let size = SIZE + 2 * QUIET_ZONE;
+        let light = |row: usize, col: usize| {
+            let (Some(row), Some(col)) = (row.checked_sub(QUIET_ZONE), col.checked_sub(QUIET_ZONE))
+            else {
+                return true;
+            };
+            row >= SIZE || col >= SIZE || !self.modules[row][col]
+        };
+        let mut out = String::new();
+        for row in (0..size).step_by(2) {
+            for col in 0..size {
+                out.push(match (light(row, col), light(row + 1, col)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    fn set(&mut self, function: &mut [[bool; SIZE]; SIZE], row: usize, col: usize, dark: bool) {
+        self.modules[row][col] = dark;
+        function[row][col] = true;
+    }
+
+    fn draw_function_patterns(&mut self, function: &mut [[bool; SIZE]; SIZE]) {
+        for i in 0..SIZE {
+            self.set(function, 6, i, i.is_multiple_of(2));
+            self.set(function, i, 6, i.is_multiple_of(2));
+        }
+        for (row, col) in [(3, 3), (3, SIZE - 4), (SIZE - 4, 3)] {
+            self.draw_finder_pattern(function, row, col);
+        }
+        self.draw_format_bits(function);
+    }
+
+    /// Draws a finder pattern and its separator around the given center
+    fn draw_finder_pattern(
+        &mut self,
+        function: &mut [[bool; SIZE]; SIZE],
+        center_row: usize,
+        center_col: usize,
+    ) {
+        for dy in -4i32..=4 {
+            for dx in -4i32..=4 {
+                let row = center_row as i32 + dy;
+                let col = center_col as i32 + dx;
+                if !(0..SIZE as i32).contains(&row) || !(0..SIZE as i32).contains(&col) {
+                    continue;
+                }
+                let distance = dx.abs().max(dy.abs());
+                self.set(
+                    function,
+                    row as usize,
+                    col as usize,
+                    distance != 2 && distance != 4,
+                );
+            }
+        }
+    }
+
+    /// Draws both copies of the format information for medium error
+    /// correction and mask pattern 0, as well as the dark module
+    fn draw_format_bits(&mut self, function: &mut [[bool; SIZE]; SIZE]) {
+        // Medium error correction is 00, followed by the mask pattern
+        let data: u32 = 0b00_000;
+        let mut remainder = data;
+        for _ in 0..10 {
+            remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+        }
+        let bits = (data << 10 | remainder) ^ 0x5412;
+        let bit = |i: usize| (bits >> i) & 1 != 0;
+        for i in 0..6 {
+            self.set(function, i, 8, bit(i));
+        }
+        self.set(function, 7, 8, bit(6));
+        self.set(function, 8, 8, bit(7));
+        self.set(function, 8, 7, bit(8));
+        for i in 9..15 {
+            self.set(function, 8, 14 - i, bit(i));
+        }
+        for i in 0..8 {
+            self.set(function, 8, SIZE - 1 - i, bit(i));
+        }
+        for i in 8..15 {
+            self.set(function, SIZE - 15 + i, 8, bit(i));
+        }
+        self.set(function, SIZE - 8, 8, true);
+    }
+
+    /// Places the codewords in the zigzag order of the standard, masking
+    /// every module whose row and column add up to an even number
+    fn draw_codewords(&mut self, codewords: &[u8], function: &[[bool; SIZE]; SIZE]) {
+        let mut i = 0;
+        let mut right = SIZE - 1;
+        while right >= 1 {
+            if right == 6 {
+                right = 5;
+            }
+            for vert in 0..SIZE {
+                for j in 0..2 {
+                    let col = right - j;
+                    let upward = (right + 1) & 2 == 0;
+                    let row = if upward { SIZE - 1 - vert } else { vert };
+                    if function[row][col] {
+                        continue;
+                    }
+                    let dark =
+                        i < codewords.len() * 8 && (codewords[i / 8] >> (7 - i % 8)) & 1 != 0;
+                    self.modules[row][col] = dark ^ (row + col).is_multiple_of(2);
+                    i += 1;
+                }
+            }
+            if right < 2 {
+                break;
+            }
+            right -= 2;
+        }
+    }
+}
+
+/// Encodes digits in numeric mode and pads them to the data capacity
+fn data_codewords(digits: &str) -> Vec<u8> {
+    fn push(bits: &mut Vec<bool>, value: u32, len: usize) {
+        bits.extend((0..len).rev().map(|i| (value >> i) & 1 != 0));
+    }
+    let mut bits = Vec::new();
+    push(&mut bits, 0b0001, 4);
+    push(&mut bits, digits.len() as u32, 10);
+    for chunk in digits.as_bytes().chunks(3) {
+        let value = chunk
+            .iter()
+            .fold(0, |acc, digit| acc * 10 + (digit - b'0') as u32);
+        push(&mut bits, value, chunk.len() * 3 + 1);
+    }
+    let terminator = (DATA_CODEWORDS * 8 - bits.len()).min(4);
+    push(&mut bits, 0, terminator);
+    let padding = (8 - bits.len() % 8) % 8;
+    push(&mut bits, 0, padding);
+    let mut codewords: Vec<u8> = bits
+        .chunks(8)
+        .map(|byte| byte.iter().fold(0, |acc, &bit| acc << 1 | bit as u8))
+        .collect();
+    for pad in [0xEC, 0x11].into_iter().cycle() {
+        if codewords.len() == DATA_CODEWORDS {
+            break;
+        }
+        codewords.push(pad);
+    }
+    codewords
+}
+
+/// Computes the error correction codewords of `data`
+fn reed_solomon_remainder(data: &[u8]) -> Vec<u8> {
+    let mut divisor = vec![0u8; EC_CODEWORDS];
+    divisor[EC_CODEWORDS - 1] = 1;
+    let mut root = 1;
+    for _ in 0..EC_CODEWORDS {
+        for j in 0..EC_CODEWORDS {
+            divisor[j] = gf_multiply(divisor[j], root);
+            if j + 1 < EC_CODEWORDS {
+                divisor[j] ^= divisor[j + 1];
+            }
+        }
+        root = gf_multiply(root, 0x02);
+    }
+    let mut remainder = vec![0u8; EC_CODEWORDS];
+    for &byte in data {
+        let factor = byte ^ remainder.remove(0);
+        remainder.push(0);
+        for (r, &d) in remainder.iter_mut().zip(&divisor) {
+            *r ^= gf_multiply(d, factor);
+        }
+    }
+    remainder
+}
+
+/// Multiplies in GF(2^8) modulo x^8 + x^4 + x^3 + x^2 + 1
+fn gf_multiply(x: u8, y: u8) -> u8 {
+    let mut z: u32 = 0;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x11D);
+        z ^= ((y as u32 >> i) & 1) * x as u32;
+    }
+    z as u8
+}
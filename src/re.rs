@@ -1,112 +1,433 @@
 pub mod rainbow;
+pub mod spill;
 
-use std::{fmt, thread};
+use std::{
+    cmp::Reverse,
+    fmt, io,
+    ops::Range,
+    path::Path,
+    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    thread,
+    time::Instant,
+};
 
-use crate::pins;
+use anyhow::{bail, Result};
+
+use crate::{
+    format::VaultFile,
+    pins::{self, MAX_PIN},
+};
+
+use self::spill::Spill;
+
+/// How `bruteforce_threaded` scores candidate masters
+#[derive(Clone, Copy, Default)]
+pub enum ScoreMode {
+    /// One point per decrypted pin that is a commonly used pin, see
+    /// `CommonPins`
+    #[default]
+    CommonPins,
+    /// One point per decrypted pin, so only validity is considered, see
+    /// `Validity`
+    ValidityOnly,
+}
+
+/// Scores the pins a candidate master decrypts to
+///
+/// Scans only ask for the score of masters under which every pin decrypts to
+/// a valid one, and report those that score above 0. Implement this to look
+/// for patterns of your own with `Cracker::bruteforce_scored`.
+pub trait Scorer: Sync {
+    /// Score of a master whose pins decrypt to `pins`, as `(id, pin)` pairs
+    /// in the order the vault stores them
+    fn score(&self, pins: &[(u8, u32)]) -> u32;
+}
+
+/// One point per pin that is in a list of commonly used pins
+pub struct CommonPins {
+    /// Sorted and without duplicates
+    common: Box<[u32]>,
+}
+
+impl CommonPins {
+    pub fn new(mut common: Vec<u32>) -> Self {
+        common.sort_unstable();
+        common.dedup();
+        Self {
+            common: common.into(),
+        }
+    }
+}
+
+/// `DEFAULT_COMMON_PINS`
+impl Default for CommonPins {
+    fn default() -> Self {
+        Self::new(DEFAULT_COMMON_PINS.into())
+    }
+}
+
+impl Scorer for CommonPins {
+    fn score(&self, pins: &[(u8, u32)]) -> u32 {
+        pins.iter()
+            .filter(|(_, pin)| self.common.binary_search(pin).is_ok())
+            .count() as u32
+    }
+}
+
+/// One point per pin, so that every master under which all pins are valid
+/// is reported
+pub struct Validity;
+
+impl Scorer for Validity {
+    fn score(&self, pins: &[(u8, u32)]) -> u32 {
+        pins.len() as u32
+    }
+}
+
+/// How a scan divides the masters among its threads
+#[derive(Clone, Copy, Default)]
+pub enum Partition {
+    /// Thread `i` of `n` scans every `n`th master starting at `i`
+    #[default]
+    Strided,
+    /// Each thread scans a contiguous range of masters
+    Contiguous,
+}
+
+/// Most threads a scan runs per available core
+const THREADS_PER_CORE: usize = 4;
+
+/// Most threads a scan should run, no matter how many are asked for
+pub fn max_thread_count() -> u32 {
+    let cores = thread::available_parallelism().map_or(1, |cores| cores.get());
+    (cores * THREADS_PER_CORE).min(u32::MAX as usize) as u32
+}
+
+/// Rejects a thread count of 0 and lowers larger ones to
+/// `max_thread_count`
+pub fn clamp_thread_count(thread_count: u32) -> Result<u32> {
+    if thread_count == 0 {
+        bail!("Thread count must be at least 1");
+    }
+    Ok(thread_count.min(max_thread_count()))
+}
+
+/// How many candidates a thread scans between progress reports
+const PROGRESS_INTERVAL: u64 = 1 << 16;
+
+/// Most suspicious masters a scan keeps in memory unless told otherwise
+pub const DEFAULT_MAX_RESULTS: usize = 10_000_000;
+
+/// Pins that `ScoreMode::CommonPins` looks for unless told otherwise
+pub const DEFAULT_COMMON_PINS: [u32; 4] = [0, 123456, 123456789, 987654321];
 
 pub struct Cracker {
+    /// Pins that scans decrypt
     pins: Box<[RawPin]>,
+    /// Every pin of the vault, including ones `with_ids` leaves out of scans
+    all_pins: Box<[RawPin]>,
+    /// Scorer of `ScoreMode::CommonPins`
+    common: CommonPins,
+    /// Percentage of the time each thread spends scanning, if limited
+    rate_limit: Option<u8>,
+    /// Whether threads keep from reporting to stderr that they finished
+    quiet: bool,
+    /// Most suspicious masters a scan collects in memory
+    max_results: usize,
+    /// Whether the last scan found more than `max_results` masters
+    truncated: AtomicBool,
+    /// Masters that bruteforce scans consider
+    masters: Range<u32>,
 }
 
 impl Cracker {
-    pub fn load(bytes: &[u8]) -> Self {
-        assert!(!bytes.is_empty());
-        let len = bytes[0] as usize;
-        let bytes = &bytes[1..];
-        assert!(bytes.len() >= len * 5);
-        let mut pins = Vec::new();
-        for i in 0..len {
-            let id = bytes[i * 5];
-            let pin_bytes = &bytes[i * 5 + 1..];
-            let raw_pin = (pin_bytes[0] as u32) << 24
-                | (pin_bytes[1] as u32) << 16
-                | (pin_bytes[2] as u32) << 8
-                | pin_bytes[3] as u32;
-            pins.push(RawPin { id, pin: raw_pin });
-        }
-        Self { pins: pins.into() }
-    }
-
-    pub fn bruteforce_threaded(&self, thread_count: u32) -> Vec<SusMaster> {
-        thread::scope(|scope| {
-            let mut handles = Vec::new();
-            for i in 0..thread_count {
-                handles.push(scope.spawn(move || self.part_bruteforce(i, thread_count, None)));
+    /// Loads the encrypted pins of a vault, or of the first section of a
+    /// duress vault
+    ///
+    /// The structure is checked like `Pins::verify` does, failing with the
+    /// same `VaultError::Malformed` on any damage.
+    pub fn load(bytes: &[u8]) -> Result<Self> {
+        let file = VaultFile::parse(bytes)?;
+        let pins: Vec<_> = file.sections[0]
+            .records
+            .iter()
+            .map(|record| RawPin {
+                id: record.id,
+                pin: record.pin,
+            })
+            .collect();
+        let pins: Box<[RawPin]> = pins.into();
+        Ok(Self {
+            all_pins: pins.clone(),
+            pins,
+            common: CommonPins::default(),
+            rate_limit: None,
+            quiet: false,
+            max_results: DEFAULT_MAX_RESULTS,
+            truncated: AtomicBool::new(false),
+            masters: 0..MAX_PIN + 1,
+        })
+    }
+
+    /// Replaces the pins that `ScoreMode::CommonPins` scores
+    pub fn with_common_pins(mut self, common: Vec<u32>) -> Self {
+        self.common = CommonPins::new(common);
+        self
+    }
+
+    /// Restricts scanning to the pins with the given ids, so that the other
+    /// pins neither need to decrypt validly nor count towards the score
+    pub fn with_ids(mut self, ids: &[u8]) -> Result<Self> {
+        for id in ids {
+            if !self.pins.iter().any(|raw_pin| raw_pin.id == *id) {
+                bail!("Vault has no pin with id {id}");
             }
-            handles
-                .into_iter()
-                .map(thread::ScopedJoinHandle::join)
-                .filter_map(|sus| sus.ok())
-                .flatten()
-                .collect()
+        }
+        let mut pins = self.pins.into_vec();
+        pins.retain(|raw_pin| ids.contains(&raw_pin.id));
+        self.pins = pins.into();
+        Ok(self)
+    }
+
+    /// Makes each scanning thread sleep so that it is busy only about
+    /// `percent` of the time
+    ///
+    /// The threads sleep in proportion to the time they spent scanning since
+    /// their last progress report, so the actual CPU usage is approximate.
+    pub fn with_rate_limit(mut self, percent: u8) -> Self {
+        assert!((1..=100).contains(&percent));
+        self.rate_limit = Some(percent);
+        self
+    }
+
+    /// Keeps scans from printing anything to stderr
+    pub fn with_quiet(mut self) -> Self {
+        self.quiet = true;
+        self
+    }
+
+    /// Caps the suspicious masters a scan collects in memory, so that a vault
+    /// matching a huge number of them cannot exhaust it
+    ///
+    /// Masters found over the cap are dropped and `truncated` tells so.
+    /// Spilled scans keep their results on disk and are not capped.
+    pub fn with_max_results(mut self, max_results: usize) -> Self {
+        self.max_results = max_results;
+        self
+    }
+
+    /// Restricts bruteforce scans to the masters in `masters`, so that a
+    /// search can be split into parts or resumed
+    ///
+    /// `bruteforce_window` scans the window it is given regardless.
+    pub fn with_masters(mut self, masters: Range<u32>) -> Self {
+        self.masters = masters.start..masters.end.min(MAX_PIN + 1);
+        self
+    }
+
+    /// Number of masters a bruteforce scan considers
+    pub fn master_count(&self) -> u32 {
+        self.masters.end.saturating_sub(self.masters.start)
+    }
+
+    /// Whether the last scan dropped masters over the cap of
+    /// `with_max_results`
+    pub fn truncated(&self) -> bool {
+        self.truncated.load(Ordering::Relaxed)
+    }
+
+    /// Most suspicious masters a scan collects in memory
+    pub fn max_results(&self) -> usize {
+        self.max_results
+    }
+
+    /// Stored form of the pin with `id`, even if `with_ids` leaves it out of
+    /// scans, for decrypting it under a master found by one
+    pub fn raw_pin(&self, id: u8) -> Option<u32> {
+        let raw_pin = self.all_pins.iter().find(|raw_pin| raw_pin.id == id)?;
+        Some(raw_pin.pin)
+    }
+
+    /// Number of pins a scan considers, which no score can exceed
+    pub fn pin_count(&self) -> usize {
+        self.pins.len()
+    }
+
+    /// Whether `master` decrypts every known `(id, pin)` pair correctly
+    pub fn matches(&self, master: u32, known: &[(u8, u32)]) -> bool {
+        known.iter().all(|&(id, pin)| {
+            self.pins
+                .iter()
+                .find(|raw_pin| raw_pin.id == id)
+                .is_some_and(|raw_pin| pins::decrypt(master, id, raw_pin.pin) == pin)
         })
     }
 
-    fn part_bruteforce(&self, start: u32, step: u32, max: Option<u32>) -> Vec<SusMaster> {
-        let mut sus = Vec::new();
+    /// Scores every master, or those `with_masters` allows, returning the
+    /// suspicious ones ordered by `rank`
+    pub fn bruteforce_threaded(&self, thread_count: u32, mode: ScoreMode) -> Vec<SusMaster> {
+        self.bruteforce_threaded_with_progress(thread_count, mode, &|_| {})
+    }
+
+    /// Like `bruteforce_threaded`, but periodically calls `progress` with the
+    /// number of candidates scanned so far
+    pub fn bruteforce_threaded_with_progress(
+        &self,
+        thread_count: u32,
+        mode: ScoreMode,
+        progress: &(dyn Fn(u64) + Send + Sync),
+    ) -> Vec<SusMaster> {
+        self.collect_threaded(thread_count, progress, |i, sink| {
+            let start = self.masters.start.saturating_add(i);
+            self.part_bruteforce_mode(start, thread_count, self.masters.end, mode, sink)
+        })
+    }
+
+    /// Like `bruteforce_threaded`, but scores the masters with `scorer`
+    /// instead of one of the built-in modes
+    pub fn bruteforce_scored<S: Scorer>(&self, thread_count: u32, scorer: &S) -> Vec<SusMaster> {
+        self.collect_threaded(thread_count, &|_| {}, |i, sink| {
+            let start = self.masters.start.saturating_add(i);
+            self.part_bruteforce(start, thread_count, self.masters.end, scorer, sink)
+        })
+    }
+
+    /// Like `bruteforce_threaded`, but only scans the masters in `window`,
+    /// divided among the threads as `partition` says
+    pub fn bruteforce_window(
+        &self,
+        thread_count: u32,
+        mode: ScoreMode,
+        window: Range<u32>,
+        partition: Partition,
+    ) -> Vec<SusMaster> {
+        let chunk = (window.end.saturating_sub(window.start)).div_ceil(thread_count);
+        self.collect_threaded(thread_count, &|_| {}, |i, sink| {
+            let (start, step, max) = match partition {
+                Partition::Strided => (window.start.saturating_add(i), thread_count, window.end),
+                Partition::Contiguous => {
+                    let start = window.start.saturating_add(i * chunk);
+                    (start, 1, start.saturating_add(chunk).min(window.end))
+                }
+            };
+            self.part_bruteforce_mode(start, step, max, mode, sink)
+        })
+    }
+
+    /// Like `bruteforce_threaded`, but spills each thread's results to a file
+    /// in `dir` instead of keeping them in memory
+    pub fn bruteforce_spilled(
+        &self,
+        thread_count: u32,
+        mode: ScoreMode,
+        dir: &Path,
+    ) -> io::Result<Spill> {
+        Spill::run(thread_count, self.rate_limit, dir, |i, sink| {
+            let start = self.masters.start.saturating_add(i);
+            self.part_bruteforce_mode(start, thread_count, self.masters.end, mode, sink)
+        })
+    }
+
+    fn part_bruteforce_mode(
+        &self,
+        start: u32,
+        step: u32,
+        max: u32,
+        mode: ScoreMode,
+        sink: &mut Sink,
+    ) {
+        match mode {
+            ScoreMode::CommonPins => self.part_bruteforce(start, step, max, &self.common, sink),
+            ScoreMode::ValidityOnly => self.part_bruteforce(start, step, max, &Validity, sink),
+        }
+    }
+
+    fn part_bruteforce<S: Scorer>(
+        &self,
+        start: u32,
+        step: u32,
+        max: u32,
+        scorer: &S,
+        sink: &mut Sink,
+    ) {
         let mut master = start;
-        let max = max.unwrap_or(1_000_000_000);
+        let mut decrypted = Vec::with_capacity(self.pins.len());
         while master < max {
-            let mut score = 0;
-            for raw_pin in self.pins.iter() {
+            decrypted.clear();
+            let valid = self.pins.iter().all(|raw_pin| {
                 let pin = pins::decrypt(master, raw_pin.id, raw_pin.pin);
-                if pin > 999_999_999 {
-                    score = 0;
-                    break;
-                }
-                match pin {
-                    0 | 123456 | 123456789 | 987654321 => {
-                        score += 1;
-                    }
-                    _ => (),
-                }
-            }
+                decrypted.push((raw_pin.id, pin));
+                pin <= MAX_PIN
+            });
+            let score = if valid { scorer.score(&decrypted) } else { 0 };
             if score > 0 {
-                sus.push(SusMaster { master, score });
+                sink.emit(SusMaster { master, score });
             }
+            sink.tick();
             master += step;
         }
-        eprintln!(">> Thread finished.");
-        sus
+        sink.finish();
+        if !self.quiet {
+            eprintln!(">> Thread finished.");
+        }
     }
 
+    /// Finds the masters under which any of `known_pins` shows up, ordered by
+    /// `rank`
     pub fn find_threaded(&self, thread_count: u32, known_pins: &[u32]) -> Vec<SusMaster> {
-        thread::scope(|scope| {
-            let mut handles = Vec::new();
-            for i in 0..thread_count {
-                handles.push(scope.spawn(move || {
-                    if known_pins.is_empty() {
-                        self.part_find_no_known(i, thread_count, None)
-                    } else {
-                        self.part_find(i, thread_count, None, known_pins)
-                    }
-                }));
-            }
-            handles
-                .into_iter()
-                .map(thread::ScopedJoinHandle::join)
-                .filter_map(|sus| sus.ok())
-                .flatten()
-                .collect()
+        self.find_threaded_with_progress(thread_count, known_pins, &|_| {})
+    }
+
+    /// Like `find_threaded`, but periodically calls `progress` with the number
+    /// of candidates scanned so far
+    pub fn find_threaded_with_progress(
+        &self,
+        thread_count: u32,
+        known_pins: &[u32],
+        progress: &(dyn Fn(u64) + Send + Sync),
+    ) -> Vec<SusMaster> {
+        self.collect_threaded(thread_count, progress, |i, sink| {
+            self.part_find_any(i, thread_count, known_pins, sink)
         })
     }
 
+    /// Like `find_threaded`, but spills each thread's results to a file in
+    /// `dir` instead of keeping them in memory
+    pub fn find_spilled(
+        &self,
+        thread_count: u32,
+        known_pins: &[u32],
+        dir: &Path,
+    ) -> io::Result<Spill> {
+        Spill::run(thread_count, self.rate_limit, dir, |i, sink| {
+            self.part_find_any(i, thread_count, known_pins, sink)
+        })
+    }
+
+    fn part_find_any(&self, start: u32, step: u32, known_pins: &[u32], sink: &mut Sink) {
+        if known_pins.is_empty() {
+            self.part_find_no_known(start, step, None, sink)
+        } else {
+            self.part_find(start, step, None, known_pins, sink)
+        }
+    }
+
     fn part_find(
         &self,
         start: u32,
         step: u32,
         max: Option<u32>,
         known_pins: &[u32],
-    ) -> Vec<SusMaster> {
+        sink: &mut Sink,
+    ) {
         assert!(!known_pins.is_empty());
-        let mut sus = Vec::new();
         let mut master = start;
         let max = max.unwrap_or(1_000_000_000);
         while master < max {
             let mut score = 0;
             for raw_pin in self.pins.iter() {
                 let pin = pins::decrypt(master, raw_pin.id, raw_pin.pin);
-                if pin > 999_999_999 {
+                if pin > MAX_PIN {
                     score = 0;
                     break;
                 }
@@ -115,38 +436,222 @@ impl Cracker {
                 }
             }
             if score > 0 {
-                sus.push(SusMaster { master, score });
+                sink.emit(SusMaster { master, score });
             }
+            sink.tick();
             master += step;
         }
-        eprintln!(">> Thread finished.");
-        sus
+        sink.finish();
+        if !self.quiet {
+            eprintln!(">> Thread finished.");
+        }
     }
 
-    fn part_find_no_known(&self, start: u32, step: u32, max: Option<u32>) -> Vec<SusMaster> {
-        let mut sus = Vec::new();
+    fn part_find_no_known(&self, start: u32, step: u32, max: Option<u32>, sink: &mut Sink) {
         let mut master = start;
         let max = max.unwrap_or(1_000_000_000);
         while master < max {
             let mut score = 1;
             for raw_pin in self.pins.iter() {
                 let pin = pins::decrypt(master, raw_pin.id, raw_pin.pin);
-                if pin > 999_999_999 {
+                if pin > MAX_PIN {
                     score = 0;
                     break;
                 }
             }
             if score > 0 {
-                sus.push(SusMaster { master, score });
+                sink.emit(SusMaster { master, score });
             }
+            sink.tick();
             master += step;
         }
-        eprintln!(">> Thread finished.");
-        sus
+        sink.finish();
+        if !self.quiet {
+            eprintln!(">> Thread finished.");
+        }
+    }
+}
+
+/// Parses a list of common pins, one per line
+///
+/// Blank lines and lines starting with `#` are ignored.
+pub fn parse_common_pins(text: &str) -> Result<Vec<u32>> {
+    let mut common = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Ok(pin) = line.parse::<u32>() else {
+            bail!("Line {}: not a pin: {line}", i + 1);
+        };
+        if pin > MAX_PIN {
+            bail!("Line {}: pin is too large: {pin} > {MAX_PIN}", i + 1);
+        }
+        common.push(pin);
+    }
+    Ok(common)
+}
+
+/// Where a thread scanning part of the masters reports to
+struct Sink<'a> {
+    emit: &'a mut dyn FnMut(SusMaster),
+    progress: &'a Progress<'a>,
+    /// Candidates scanned since the last progress report
+    scanned: u64,
+    /// See `Cracker::with_rate_limit`
+    rate_limit: Option<u8>,
+    /// When the thread last woke up from throttling
+    busy_since: Instant,
+}
+
+impl<'a> Sink<'a> {
+    fn new(
+        emit: &'a mut dyn FnMut(SusMaster),
+        progress: &'a Progress<'a>,
+        rate_limit: Option<u8>,
+    ) -> Self {
+        Self {
+            emit,
+            progress,
+            scanned: 0,
+            rate_limit,
+            busy_since: Instant::now(),
+        }
+    }
+
+    fn emit(&mut self, sus: SusMaster) {
+        (self.emit)(sus);
+    }
+
+    /// Counts a scanned candidate, reporting progress every so often
+    fn tick(&mut self) {
+        self.scanned += 1;
+        if self.scanned == PROGRESS_INTERVAL {
+            self.finish();
+            self.throttle();
+        }
+    }
+
+    /// Sleeps long enough that the time spent scanning since the last call
+    /// makes up the limited percentage of the time passed
+    fn throttle(&mut self) {
+        let Some(percent) = self.rate_limit else {
+            return;
+        };
+        let busy = self.busy_since.elapsed();
+        thread::sleep(busy * (100 - percent) as u32 / percent as u32);
+        self.busy_since = Instant::now();
+    }
+
+    /// Reports the candidates scanned since the last report
+    fn finish(&mut self) {
+        if self.scanned > 0 {
+            self.progress.advance(self.scanned);
+            self.scanned = 0;
+        }
+    }
+}
+
+/// Number of candidates scanned by all threads together
+struct Progress<'a> {
+    scanned: AtomicU64,
+    callback: &'a (dyn Fn(u64) + Send + Sync),
+}
+
+impl<'a> Progress<'a> {
+    fn new(callback: &'a (dyn Fn(u64) + Send + Sync)) -> Self {
+        Self {
+            scanned: AtomicU64::new(0),
+            callback,
+        }
+    }
+
+    fn advance(&self, scanned: u64) {
+        let total = self.scanned.fetch_add(scanned, Ordering::Relaxed) + scanned;
+        (self.callback)(total);
     }
 }
 
+impl Cracker {
+    /// Runs `part` on `thread_count` threads and collects what they emit, up
+    /// to `max_results` masters
+    ///
+    /// Each thread sorts its own results, so that only merging them is left.
+    fn collect_threaded<F>(
+        &self,
+        thread_count: u32,
+        progress: &(dyn Fn(u64) + Send + Sync),
+        part: F,
+    ) -> Vec<SusMaster>
+    where
+        F: Fn(u32, &mut Sink) + Sync,
+    {
+        let part = &part;
+        let progress = &Progress::new(progress);
+        let collected = &AtomicUsize::new(0);
+        self.truncated.store(false, Ordering::Relaxed);
+        thread::scope(|scope| {
+            let mut handles = Vec::new();
+            for i in 0..thread_count {
+                handles.push(scope.spawn(move || {
+                    let mut sus = Vec::new();
+                    let mut emit = |master| {
+                        if collected.fetch_add(1, Ordering::Relaxed) < self.max_results {
+                            sus.push(master);
+                        } else {
+                            self.truncated.store(true, Ordering::Relaxed);
+                        }
+                    };
+                    part(i, &mut Sink::new(&mut emit, progress, self.rate_limit));
+                    sus.sort_by_key(rank);
+                    sus
+                }));
+            }
+            merge_ranked(
+                handles
+                    .into_iter()
+                    .map(thread::ScopedJoinHandle::join)
+                    .filter_map(|sus| sus.ok())
+                    .collect(),
+            )
+        })
+    }
+}
+
+/// Order of scan results, highest score first
+///
+/// Masters with the same score stay in the order the threads found them.
+pub fn rank(sus: &SusMaster) -> Reverse<u32> {
+    Reverse(sus.score)
+}
+
+/// Merges lists that are each ordered by `rank` into one, taking masters with
+/// the same score from the lists in order
+///
+/// Scores are few, so this takes a pass per score rather than a heap.
+pub fn merge_ranked(parts: Vec<Vec<SusMaster>>) -> Vec<SusMaster> {
+    let mut merged = Vec::with_capacity(parts.iter().map(Vec::len).sum());
+    let mut parts: Vec<_> = parts
+        .into_iter()
+        .map(|part| part.into_iter().peekable())
+        .collect();
+    while let Some(score) = parts
+        .iter_mut()
+        .filter_map(|part| Some(part.peek()?.score))
+        .max()
+    {
+        for part in &mut parts {
+            while let Some(sus) = part.next_if(|sus| sus.score == score) {
+                merged.push(sus);
+            }
+        }
+    }
+    merged
+}
+
 /// Encrypted pin
+#[derive(Clone)]
 struct RawPin {
     id: u8,
     pin: u32,
@@ -157,8 +662,19 @@ pub struct SusMaster {
     pub score: u32,
 }
 
+/// Formats as `master [score=n]`
+///
+/// The alternate form `{:#}` adds the master in hex and notes whether it
+/// looks like a date, to help spot a master a human picked.
 impl fmt::Display for SusMaster {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:9} [score={}]", self.master, self.score)
+        write!(f, "{:9} [score={}]", self.master, self.score)?;
+        if f.alternate() {
+            write!(f, " [hex={:#010x}]", self.master)?;
+            if pins::looks_like_date(self.master) {
+                write!(f, " [date]")?;
+            }
+        }
+        Ok(())
     }
 }
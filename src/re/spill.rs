@@ -0,0 +1,174 @@
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs::{self, DirBuilder, File, OpenOptions},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::Ordering,
+    thread,
+};
+
+use super::{Progress, Sink, SusMaster};
+
+/// Results of a cracking run that were written to one file per thread
+///
+/// Each file holds the thread's results as big endian `(master, score)`
+/// pairs in ascending master order, so they can be merged without loading
+/// them into memory. The files are kept in a directory that only the user can
+/// open, which is removed with them when the `Spill` is dropped.
+pub struct Spill {
+    dir: PathBuf,
+    files: Vec<PathBuf>,
+    /// Candidates scanned by all threads together
+    scanned: u64,
+}
+
+impl Spill {
+    /// Runs `part` on `thread_count` threads, writing everything they emit to
+    /// a file per thread in a new directory in `dir`
+    pub(super) fn run<F>(
+        thread_count: u32,
+        rate_limit: Option<u8>,
+        dir: &Path,
+        part: F,
+    ) -> io::Result<Self>
+    where
+        F: Fn(u32, &mut Sink) + Sync,
+    {
+        let part = &part;
+        let progress = &Progress::new(&|_| {});
+        let dir = create_private_dir(dir)?;
+        let files: Vec<_> = (0..thread_count)
+            .map(|i| dir.join(format!("{i}.spill")))
+            .collect();
+        // Created up front so that dropping cleans up after failed threads too
+        let mut spill = Self {
+            dir,
+            files,
+            scanned: 0,
+        };
+        let results: Vec<io::Result<()>> = thread::scope(|scope| {
+            let handles: Vec<_> = spill
+                .files
+                .iter()
+                .zip(0..)
+                .map(|(file, i)| {
+                    scope.spawn(move || {
+                        // Never opens a file that someone else put there
+                        let file = OpenOptions::new().write(true).create_new(true).open(file)?;
+                        let mut out = BufWriter::new(file);
+                        let mut result = Ok(());
+                        let mut emit = |sus| {
+                            if result.is_ok() {
+                                result = write_record(&mut out, &sus);
+                            }
+                        };
+                        part(i, &mut Sink::new(&mut emit, progress, rate_limit));
+                        result?;
+                        out.flush()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|_| Err(io::Error::other("Cracking thread panicked")))
+                })
+                .collect()
+        });
+        for result in results {
+            result?;
+        }
+        spill.scanned = progress.scanned.load(Ordering::Relaxed);
+        Ok(spill)
+    }
+
+    /// Number of candidates the run scanned
+    pub fn scanned(&self) -> u64 {
+        self.scanned
+    }
+
+    /// Merges the spilled results in ascending master order, dropping
+    /// duplicate masters, and returns how many results were emitted
+    pub fn merge(&self, mut emit: impl FnMut(SusMaster) -> io::Result<()>) -> io::Result<usize> {
+        let mut readers = Vec::new();
+        for file in &self.files {
+            readers.push(BufReader::new(File::open(file)?));
+        }
+        let mut heap = BinaryHeap::new();
+        for (i, reader) in readers.iter_mut().enumerate() {
+            if let Some(sus) = read_record(reader)? {
+                heap.push(Reverse((sus.master, sus.score, i)));
+            }
+        }
+        let mut count = 0;
+        let mut last: Option<SusMaster> = None;
+        while let Some(Reverse((master, score, i))) = heap.pop() {
+            if let Some(next) = read_record(&mut readers[i])? {
+                heap.push(Reverse((next.master, next.score, i)));
+            }
+            match &mut last {
+                Some(last) if last.master == master => last.score = last.score.max(score),
+                _ => {
+                    if let Some(last) = last.replace(SusMaster { master, score }) {
+                        emit(last)?;
+                        count += 1;
+                    }
+                }
+            }
+        }
+        if let Some(last) = last {
+            emit(last)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+impl Drop for Spill {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Creates a new directory in `dir` for the files of a run, which on Unix
+/// only the user can open
+///
+/// Creating fails rather than reusing a directory that already exists, so a
+/// name that was guessed in a shared `dir` is skipped.
+fn create_private_dir(dir: &Path) -> io::Result<PathBuf> {
+    let mut builder = DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    loop {
+        let path = dir.join(format!(
+            "vcry-{}-{:08x}",
+            process::id(),
+            rand::random::<u32>()
+        ));
+        match builder.create(&path) {
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            result => return result.map(|()| path),
+        }
+    }
+}
+
+fn write_record(out: &mut impl Write, sus: &SusMaster) -> io::Result<()> {
+    out.write_all(&sus.master.to_be_bytes())?;
+    out.write_all(&sus.score.to_be_bytes())
+}
+
+fn read_record(reader: &mut impl Read) -> io::Result<Option<SusMaster>> {
+    let mut record = [0; 8];
+    match reader.read_exact(&mut record) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    let master = u32::from_be_bytes([record[0], record[1], record[2], record[3]]);
+    let score = u32::from_be_bytes([record[4], record[5], record[6], record[7]]);
+    Ok(Some(SusMaster { master, score }))
+}
@@ -0,0 +1,254 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::{
+    prelude::*,
+    symbols::border,
+    widgets::{block::*, *},
+};
+use vault_crypt::{
+    pins::MAX_PIN,
+    re::{Cracker, SusMaster},
+};
+
+use crate::tui;
+
+/// How often the progress gauge is redrawn while searching
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Guides through finding a forgotten master from pins the user remembers,
+/// then offers to open the vault with one of the candidates
+pub struct RecoverApp {
+    cracker: Arc<Cracker>,
+    thread_count: u32,
+    /// Remembered pins, which may be at any position in the vault
+    known: Vec<u32>,
+    input: String,
+    stage: Stage,
+    state: ListState,
+    /// Master picked to open the vault with
+    chosen: Option<u32>,
+    exit: bool,
+}
+
+enum Stage {
+    /// Asking for remembered pins
+    Asking,
+    Searching {
+        /// Candidates scanned so far by all threads
+        scanned: Arc<AtomicU64>,
+        handle: JoinHandle<Vec<SusMaster>>,
+    },
+    /// Candidates of the finished search, best first
+    Results(Vec<SusMaster>),
+}
+
+impl RecoverApp {
+    pub fn new(cracker: Cracker, thread_count: u32) -> Self {
+        Self {
+            cracker: Arc::new(cracker),
+            thread_count,
+            known: Vec::new(),
+            input: String::new(),
+            stage: Stage::Asking,
+            state: ListState::default(),
+            chosen: None,
+            exit: false,
+        }
+    }
+
+    /// Runs the wizard, returning the master picked to open the vault with
+    pub fn run(&mut self, terminal: &mut tui::Tui) -> Result<Option<u32>> {
+        while !self.exit {
+            terminal.draw(|frame| frame.render_widget(&mut *self, frame.size()))?;
+            self.handle_events()?;
+            self.check_search();
+        }
+        Ok(self.chosen)
+    }
+
+    fn handle_events(&mut self) -> Result<()> {
+        if !event::poll(POLL_INTERVAL)? {
+            return Ok(());
+        }
+        match event::read()? {
+            Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
+                self.handle_key_event(key_event);
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn handle_key_event(&mut self, key_event: KeyEvent) {
+        match (&self.stage, key_event.code) {
+            (Stage::Asking, KeyCode::Char(c @ '0'..='9')) if self.input.len() < 9 => {
+                self.input.push(c);
+            }
+            (Stage::Asking, KeyCode::Backspace) if !self.input.is_empty() => {
+                self.input.pop();
+            }
+            (Stage::Asking, KeyCode::Backspace) => {
+                self.known.pop();
+            }
+            (Stage::Asking, KeyCode::Enter) if !self.input.is_empty() => {
+                let pin: u32 = self.input.parse().expect("Input should be digits");
+                if pin <= MAX_PIN && !self.known.contains(&pin) {
+                    self.known.push(pin);
+                }
+                self.input.clear();
+            }
+            (Stage::Asking, KeyCode::Enter) if !self.known.is_empty() => self.start_search(),
+            (Stage::Results(results), KeyCode::Up) if !results.is_empty() => {
+                self.state.select(Some(
+                    self.state.selected().map_or(0, |i| i.saturating_sub(1)),
+                ));
+            }
+            (Stage::Results(results), KeyCode::Down) if !results.is_empty() => {
+                self.state.select(Some(
+                    self.state
+                        .selected()
+                        .map_or(0, |i| (i + 1).min(results.len() - 1)),
+                ));
+            }
+            (Stage::Results(results), KeyCode::Enter) => {
+                if let Some(i) = self.state.selected() {
+                    self.chosen = Some(results[i].master);
+                    self.exit = true;
+                }
+            }
+            (Stage::Results(_), KeyCode::Backspace) => {
+                self.stage = Stage::Asking;
+                self.state.select(None);
+            }
+            (_, KeyCode::Esc | KeyCode::Char('q')) => self.exit = true,
+            _ => (),
+        }
+    }
+
+    /// Searches for masters under which any remembered pin shows up, in the
+    /// background so that progress can be shown
+    fn start_search(&mut self) {
+        let cracker = Arc::clone(&self.cracker);
+        let scanned = Arc::new(AtomicU64::new(0));
+        let progress = Arc::clone(&scanned);
+        let thread_count = self.thread_count;
+        let known = self.known.clone();
+        let handle = thread::spawn(move || {
+            cracker.find_threaded_with_progress(thread_count, &known, &|total| {
+                progress.fetch_max(total, Ordering::Relaxed);
+            })
+        });
+        self.stage = Stage::Searching { scanned, handle };
+    }
+
+    /// Moves on to the results once the search finished
+    fn check_search(&mut self) {
+        if !matches!(&self.stage, Stage::Searching { handle, .. } if handle.is_finished()) {
+            return;
+        }
+        let Stage::Searching { handle, .. } = std::mem::replace(&mut self.stage, Stage::Asking)
+        else {
+            unreachable!();
+        };
+        // A panicked search found nothing that could be trusted
+        let results = handle.join().unwrap_or_default();
+        self.state.select((!results.is_empty()).then_some(0));
+        self.stage = Stage::Results(results);
+    }
+}
+
+impl Widget for &mut RecoverApp {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let title = Title::from("Recover master".bold());
+        let mut instructions = match &self.stage {
+            Stage::Asking => vec![
+                " Add pin".into(),
+                "<Enter>".blue().bold(),
+                " Remove last".into(),
+                "<Backspace>".blue().bold(),
+            ],
+            Stage::Searching { .. } => Vec::new(),
+            Stage::Results(_) => vec![
+                " Open vault".into(),
+                "<Enter>".blue().bold(),
+                " Change pins".into(),
+                "<Backspace>".blue().bold(),
+            ],
+        };
+        instructions.push(" Quit".into());
+        instructions.push("<Q>".blue().bold());
+        let block = Block::default()
+            .title(title.alignment(Alignment::Center))
+            .title(
+                Title::from(Line::from(instructions))
+                    .alignment(Alignment::Center)
+                    .position(Position::Bottom),
+            )
+            .borders(Borders::ALL)
+            .border_set(border::THICK);
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let [known_area, body_area] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(inner);
+        let known: Vec<_> = self.known.iter().map(u32::to_string).collect();
+        Paragraph::new(format!("Remembered pins: {}", known.join(", "))).render(known_area, buf);
+        match &self.stage {
+            Stage::Asking => {
+                let mut text = Text::from(format!("Add pin: {}_", self.input));
+                text.push_line("");
+                text.push_line(
+                    "Enter pins you remember from this vault, in any order. Press Enter on an \
+                     empty line to search every master for them, which takes a while."
+                        .dark_gray(),
+                );
+                Paragraph::new(text)
+                    .wrap(Wrap { trim: true })
+                    .render(body_area, buf);
+            }
+            Stage::Searching { scanned, .. } => {
+                let scanned = scanned.load(Ordering::Relaxed);
+                let ratio = (scanned as f64 / (MAX_PIN as f64 + 1.0)).min(1.0);
+                let [gauge_area, _] =
+                    Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(body_area);
+                Gauge::default()
+                    .gauge_style(Style::default().green())
+                    .label(format!(
+                        "Searching with {} thread(s): {:.1}%",
+                        self.thread_count,
+                        ratio * 100.0
+                    ))
+                    .ratio(ratio)
+                    .render(gauge_area, buf);
+            }
+            Stage::Results(results) if results.is_empty() => {
+                Paragraph::new("No master shows any of these pins. Check them and try again.")
+                    .render(body_area, buf);
+            }
+            Stage::Results(results) => {
+                StatefulWidget::render(
+                    List::new(
+                        results.iter().map(|sus| {
+                            format!("{:9}  {} pin(s) recognized", sus.master, sus.score)
+                        }),
+                    )
+                    .highlight_style(Style::default().green())
+                    .highlight_symbol(">>")
+                    .direction(ListDirection::TopToBottom),
+                    body_area,
+                    buf,
+                    &mut self.state,
+                );
+            }
+        }
+    }
+}
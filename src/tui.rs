@@ -1,22 +1,66 @@
-use std::io::{stdout, Stdout};
+use std::{
+    fmt,
+    io::{stdout, Stdout},
+};
 
 use anyhow::Result;
 use crossterm::{
+    event::{DisableFocusChange, EnableFocusChange},
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    terminal::{
+        disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen, SetTitle,
+    },
+    Command,
 };
 use ratatui::{backend::CrosstermBackend, Terminal};
 
 pub type Tui = Terminal<CrosstermBackend<Stdout>>;
 
-pub fn init() -> Result<Tui> {
+/// Sets up the terminal, asking it to report focus changes if `focus_events`
+pub fn init(title: &str, focus_events: bool) -> Result<Tui> {
     execute!(stdout(), EnterAlternateScreen)?;
     enable_raw_mode()?;
+    // Not every terminal supports titles, which is no reason to fail
+    let _ = execute!(stdout(), PushTitle, SetTitle(title));
+    // Likewise for focus events
+    if focus_events {
+        let _ = execute!(stdout(), EnableFocusChange);
+    }
     Ok(Terminal::new(CrosstermBackend::new(stdout()))?)
 }
 
 pub fn restore() -> Result<()> {
+    // Turning focus events off is harmless when they were never on
+    let _ = execute!(stdout(), DisableFocusChange, PopTitle);
     execute!(stdout(), LeaveAlternateScreen)?;
     disable_raw_mode()?;
     Ok(())
 }
+
+/// Saves the current window title on the terminal's title stack
+struct PushTitle;
+
+impl Command for PushTitle {
+    fn write_ansi(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        f.write_str("\x1B[22;0t")
+    }
+
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Restores the window title saved by `PushTitle`
+struct PopTitle;
+
+impl Command for PopTitle {
+    fn write_ansi(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        f.write_str("\x1B[23;0t")
+    }
+
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
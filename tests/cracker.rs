@@ -0,0 +1,175 @@
+//! Setting up scans with `re`
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use vault_crypt::{
+    pins::{self, Pins},
+    re::{self, Cracker, Partition, ScoreMode, Scorer},
+};
+
+#[test]
+fn thread_count_is_checked() {
+    assert!(re::clamp_thread_count(0).is_err());
+    assert_eq!(re::clamp_thread_count(1).unwrap(), 1);
+    assert_eq!(
+        re::clamp_thread_count(100_000).unwrap(),
+        re::max_thread_count()
+    );
+    assert!(re::max_thread_count() < 100_000);
+}
+
+#[test]
+fn results_are_capped() {
+    let mut pins = Pins::new(1234);
+    pins.add(4242);
+    let cracker = Cracker::load(&pins.save().unwrap())
+        .unwrap()
+        .with_max_results(10);
+    let scan = |cracker: &Cracker| {
+        cracker.bruteforce_window(2, ScoreMode::ValidityOnly, 0..10_000, Partition::Strided)
+    };
+    assert_eq!(scan(&cracker).len(), 10);
+    assert!(cracker.truncated());
+    let cracker = cracker.with_max_results(re::DEFAULT_MAX_RESULTS);
+    assert!(scan(&cracker).len() > 10);
+    assert!(!cracker.truncated());
+}
+
+#[test]
+fn results_are_ranked() {
+    let mut pins = Pins::new(1234);
+    pins.add(4242);
+    pins.add(123_456);
+    let cracker = Cracker::load(&pins.save().unwrap()).unwrap();
+    let sus = cracker.bruteforce_window(3, ScoreMode::ValidityOnly, 0..20_000, Partition::Strided);
+    assert!(sus.len() > 100);
+    assert!(sus
+        .windows(2)
+        .all(|pair| re::rank(&pair[0]) <= re::rank(&pair[1])));
+    assert!(sus.iter().any(|sus| sus.score == 2));
+}
+
+#[test]
+fn bruteforce_stays_in_range() {
+    let mut pins = Pins::new(1234);
+    pins.add(4242);
+    let cracker = Cracker::load(&pins.save().unwrap())
+        .unwrap()
+        .with_masters(5_000..15_000);
+    assert_eq!(cracker.master_count(), 10_000);
+    let sus = cracker.bruteforce_threaded(3, ScoreMode::ValidityOnly);
+    assert!(!sus.is_empty());
+    assert!(sus.iter().all(|sus| (5_000..15_000).contains(&sus.master)));
+}
+
+/// Scores masters under which a pin decrypts to a date written as DDMMYYYY
+struct Dates;
+
+impl Scorer for Dates {
+    fn score(&self, pins: &[(u8, u32)]) -> u32 {
+        let is_date = |pin: u32| {
+            let (day, month, year) = (pin / 1_000_000, pin / 10_000 % 100, pin % 10_000);
+            (1..=31).contains(&day) && (1..=12).contains(&month) && (1900..=2099).contains(&year)
+        };
+        pins.iter().filter(|(_, pin)| is_date(*pin)).count() as u32
+    }
+}
+
+#[test]
+fn custom_scorers_are_used() {
+    let mut pins = Pins::new(1234);
+    // 24 December 1990
+    pins.add(24121990);
+    pins.add(4242);
+    let cracker = Cracker::load(&pins.save().unwrap())
+        .unwrap()
+        .with_masters(0..5_000);
+    let sus = cracker.bruteforce_scored(2, &Dates);
+    assert!(sus.iter().any(|sus| sus.master == 1234 && sus.score == 1));
+    for sus in &sus {
+        let pin = pins::decrypt(sus.master, 0, cracker.raw_pin(0).unwrap());
+        let other = pins::decrypt(sus.master, 1, cracker.raw_pin(1).unwrap());
+        assert_eq!(sus.score, Dates.score(&[(0, pin), (1, other)]));
+    }
+}
+
+/// Damaged vaults that a loader must reject rather than panic on
+const MALFORMED: &[&[u8]] = &[
+    // Empty and header only
+    &[],
+    &[0xFF],
+    &[0xFF, 0x09],
+    // Counts larger than the records that follow
+    &[0x01],
+    &[0x02, 0x00, 0x00, 0x00, 0x00, 0x01],
+    &[0xFE, 0x00, 0x00, 0x00, 0x00, 0x01],
+    &[0xFF, 0x02, 0xFF],
+    // Truncated record
+    &[0x01, 0x00, 0x00, 0x00],
+    // Id out of range and duplicate ids
+    &[0x01, 0x64, 0x00, 0x00, 0x00, 0x01],
+    &[0x02, 0x00, 0, 0, 0, 1, 0x00, 0, 0, 0, 2],
+    // Metadata length missing, and larger than what follows
+    &[0xFF, 0x02, 0x00],
+    &[0xFF, 0x02, 0x00, 0xFF, 0xFF, 0x41],
+    // Duress sections missing, oversized and sharing a tag
+    &[0xFF, 0x03],
+    &[0xFF, 0x03, 0x07, 0xFF, 0xFF, 0xFF, 0xFF, 0x00],
+    &[
+        0xFF, 0x03, 0x07, 0, 0, 0, 3, 0, 0, 0, 0x07, 0, 0, 0, 3, 0, 0, 0,
+    ],
+    // History that is not made of whole entries
+    &[0xFF, 0x04, 0x00, 0x00, 0x00, 0x00, 0x02, 0x01, 0x02],
+    // Text secrets longer than their block
+    &[
+        0xFF, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x00,
+    ],
+    // Trailing bytes
+    &[0x00, 0x00],
+];
+
+#[test]
+fn malformed_vaults_are_rejected() {
+    for bytes in MALFORMED {
+        assert!(Pins::verify(bytes).is_err(), "{bytes:?}");
+        assert!(Cracker::load(bytes).is_err(), "{bytes:?}");
+    }
+}
+
+#[test]
+fn mutated_vaults_never_panic() {
+    let mut pins = Pins::new(1234);
+    pins.add(4242);
+    pins.add(123_456);
+    let plain = pins.save().unwrap();
+    pins.set(0, 5555);
+    pins.add_text("correct horse");
+    pins.set_metadata("bank");
+    let full = pins.save().unwrap();
+    pins.add_duress(4321).unwrap();
+    let duress = pins.save().unwrap();
+
+    let mut rng = StdRng::seed_from_u64(473);
+    for valid in [plain, full, duress] {
+        for _ in 0..2_000 {
+            let mut bytes = valid.clone();
+            for _ in 0..rng.gen_range(1..=3) {
+                match rng.gen_range(0..3) {
+                    0 => {
+                        let i = rng.gen_range(0..bytes.len());
+                        bytes[i] = rng.gen();
+                    }
+                    1 => bytes.truncate(rng.gen_range(0..bytes.len())),
+                    _ => bytes.push(rng.gen()),
+                }
+                if bytes.is_empty() {
+                    break;
+                }
+            }
+            let verified = Pins::verify(&bytes).is_ok();
+            assert_eq!(Cracker::load(&bytes).is_ok(), verified, "{bytes:?}");
+            if verified {
+                let _ = Pins::load_checked(&bytes, 1234);
+            }
+        }
+    }
+}
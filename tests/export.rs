@@ -0,0 +1,21 @@
+//! Exporting pins to other password managers
+
+use vault_crypt::{
+    export::{self, ExportFormat},
+    pins::Pins,
+};
+
+#[test]
+fn keepass_csv_skips_empty_entries() {
+    let mut pins = Pins::new(1234);
+    pins.add(4242);
+    pins.add(0);
+    pins.add_text("a, \"quoted\" secret");
+    pins.add_text("");
+    assert_eq!(
+        export::export(&pins, ExportFormat::KeepassCsv),
+        "Title,Username,Password,URL,Notes\r\n\
+         Pin 0,,4242,,\r\n\
+         Pin 2,,\"a, \"\"quoted\"\" secret\",,\r\n"
+    );
+}
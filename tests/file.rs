@@ -0,0 +1,37 @@
+//! Path handling in `file`
+
+use vault_crypt::file;
+
+#[test]
+fn home_is_expanded() {
+    let home = std::env::var("HOME").unwrap();
+    assert_eq!(file::expand_home("~"), home.trim_end_matches('/'));
+    assert_eq!(
+        file::expand_home("~/vault.vcry"),
+        format!("{}/vault.vcry", home.trim_end_matches('/'))
+    );
+    assert_eq!(file::expand_home("vault.vcry"), "vault.vcry");
+    assert_eq!(file::expand_home("a/~/vault.vcry"), "a/~/vault.vcry");
+    assert_eq!(
+        file::expand_home("~no-such-user-vcry/vault.vcry"),
+        "~no-such-user-vcry/vault.vcry"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn written_vaults_keep_private_permissions() {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    let dir = std::env::temp_dir().join(format!("vcry-file-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("vault.vcry");
+    let mode = |path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    file::write_atomic(&path, b"one").unwrap();
+    assert_eq!(mode(&path), 0o600);
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+    file::write_atomic(&path, b"two").unwrap();
+    assert_eq!(mode(&path), 0o640);
+    assert_eq!(fs::read(&path).unwrap(), b"two");
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,50 @@
+//! Parsing job files with `job`
+
+use std::path::Path;
+
+use vault_crypt::job::Job;
+
+#[test]
+fn job_files_are_parsed() {
+    let mut job = Job::parse(
+        r#"
+        # Second half
+        threads = 8
+        min = 500_000_000 # inclusive
+        max = 999_999_999
+        common_file = "common.txt"
+        output = '/tmp/results.txt'
+        "#,
+    )
+    .unwrap();
+    job.resolve_paths(Path::new("jobs"));
+    assert_eq!(
+        job,
+        Job {
+            threads: Some(8),
+            min: Some(500_000_000),
+            max: Some(999_999_999),
+            common_file: Some("jobs/common.txt".to_string()),
+            output: Some("/tmp/results.txt".to_string()),
+        }
+    );
+    assert_eq!(Job::parse("").unwrap(), Job::default());
+}
+
+#[test]
+fn bad_job_files_are_rejected() {
+    for text in [
+        "threads",
+        "threads = \"8\"",
+        "output = 8",
+        "speed = 8",
+        "[crack]",
+        "min = 1_000_000_000",
+        "min = 5\nmax = 4",
+        "threads = 1\nthreads = 2",
+        "common_file = \"common.txt",
+        "common_file = \"common.txt\" trailing",
+    ] {
+        assert!(Job::parse(text).is_err(), "{text}");
+    }
+}
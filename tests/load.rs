@@ -0,0 +1,25 @@
+//! Loading vaults that are damaged or opened with the wrong master
+
+use vault_crypt::{error::VaultError, pins::Pins};
+
+#[test]
+fn corrupted_metadata_is_not_text() {
+    let mut pins = Pins::new(1234);
+    pins.add(4242);
+    pins.set_metadata("savings");
+    let mut bytes = pins.save().unwrap();
+    // Setting the high bit of an ASCII byte leaves a lone continuation byte
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0x80;
+
+    Pins::verify(&bytes).unwrap();
+    let loaded = Pins::load(&bytes, 1234);
+    assert!(loaded.metadata().starts_with("savin"));
+    assert!(loaded.metadata().ends_with('\u{FFFD}'));
+
+    let err = Pins::load_checked(&bytes, 1234).err().unwrap();
+    assert!(matches!(
+        err.downcast_ref::<VaultError>(),
+        Some(VaultError::WrongMaster)
+    ));
+}
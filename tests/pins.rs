@@ -0,0 +1,177 @@
+//! The in-memory API of `Pins`
+
+use rand::{
+    rngs::{mock::StepRng, StdRng},
+    SeedableRng,
+};
+use vault_crypt::pins::{self, Pin, PinPolicy, Pins, WeakKind, XorShift, MAX_PIN};
+
+#[test]
+fn free_ids_skip_used_ones() {
+    let mut pins = Pins::new(1234);
+    assert_eq!(pins.free_ids(), (0..=99).collect::<Vec<_>>());
+    for pin in [1111, 2222, 3333] {
+        pins.add(pin);
+    }
+    pins.remove(1);
+    let free = pins.free_ids();
+    assert_eq!(free.len(), 98);
+    assert_eq!(free[..2], [1, 3]);
+    assert_eq!(free.last(), Some(&99));
+}
+
+#[test]
+fn removed_ids_at_the_end_are_reused() {
+    let mut pins = Pins::new(1234);
+    for pin in [1111, 2222, 3333] {
+        pins.add(pin);
+    }
+    pins.remove(2);
+    pins.remove(1);
+    pins.add(4444);
+    assert_eq!(pins.iter_ids().collect::<Vec<_>>(), [0, 1]);
+    pins.remove(0);
+    pins.remove(0);
+    pins.add(5555);
+    assert_eq!(pins.iter_ids().collect::<Vec<_>>(), [0]);
+    pins.validate().unwrap();
+}
+
+#[test]
+fn removing_a_middle_pin_keeps_the_largest_id() {
+    let mut pins = Pins::new(1234);
+    for pin in [1111, 2222, 3333] {
+        pins.add(pin);
+    }
+    pins.remove(1);
+    pins.add(4444);
+    assert_eq!(pins.iter_ids().collect::<Vec<_>>(), [0, 2, 3]);
+    pins.validate().unwrap();
+}
+
+#[test]
+fn compacting_keeps_order_texts_and_history() {
+    let mut pins = Pins::new(1234);
+    for pin in [1111, 2222, 3333, 4444] {
+        pins.add(pin);
+    }
+    pins.add_text("secret");
+    pins.set(3, 5555);
+    pins.remove(2);
+    pins.remove(0);
+    assert_eq!(pins.compact_ids(), [(1, 0), (3, 1), (4, 2)]);
+    assert_eq!(pins.iter_ids().collect::<Vec<_>>(), [0, 1, 2]);
+    assert_eq!(pins.get(0).pin, 2222);
+    assert_eq!(pins.get(1).pin, 5555);
+    assert_eq!(pins.history(1), [4444]);
+    assert_eq!(pins.text(2), Some("secret"));
+    assert!(pins.compact_ids().is_empty());
+    pins.add(6666);
+    assert_eq!(pins.get(3).id, 3);
+    pins.validate().unwrap();
+}
+
+#[test]
+fn capacity_follows_the_largest_id() {
+    let mut pins = Pins::new(1234);
+    assert_eq!(pins.remaining(), 100);
+    for pin in 1..=100 {
+        assert!(pins.add(pin));
+    }
+    assert!(pins.is_full());
+    assert!(!pins.add(101));
+    pins.remove(0);
+    assert!(pins.is_full());
+    pins.remove(98);
+    assert_eq!(pins.remaining(), 1);
+    pins.compact_ids();
+    assert_eq!(pins.remaining(), 2);
+}
+
+#[test]
+fn classify_agrees_with_guessability() {
+    assert_eq!(pins::classify(0), WeakKind::AllZeros);
+    assert_eq!(pins::classify(1234), WeakKind::Common);
+    assert_eq!(pins::classify(42), WeakKind::Short);
+    assert_eq!(pins::classify(777_777), WeakKind::Repeated);
+    assert_eq!(pins::classify(345_678), WeakKind::Sequential);
+    assert_eq!(pins::classify(24_121_999), WeakKind::Date);
+    assert_eq!(pins::classify(1_121_112), WeakKind::FewDigits);
+    assert_eq!(pins::classify(524_422_338), WeakKind::Ok);
+    for pin in (0..2_000_000).step_by(7) {
+        assert_eq!(pins::is_weak(pin), pins::guessability(pin) <= 1, "{pin}");
+    }
+}
+
+#[test]
+fn generated_pins_follow_the_policy() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let policy = PinPolicy::new(4, true).unwrap();
+    for _ in 0..1_000 {
+        let pin = pins::random_pin_with_policy(&mut rng, policy);
+        assert!((1000..=9999).contains(&pin), "{pin}");
+        assert!(!pins::is_weak(pin), "{pin}");
+    }
+    assert!(PinPolicy::new(2, true).is_err());
+    assert!(PinPolicy::new(10, false).is_err());
+}
+
+#[test]
+fn master_zero_needs_allowing() {
+    assert!(pins::check_master(0, false).is_err());
+    assert!(pins::check_master(0, true).is_ok());
+    assert!(pins::check_master(1, false).is_ok());
+    // Master 0 really stores pins as they are
+    let encrypted = pins::encrypt(0, 7, 4242, &mut StepRng::new(0, 0)).unwrap();
+    assert_eq!(encrypted, 4242);
+}
+
+#[test]
+fn checked_pins_stay_in_range() {
+    assert_eq!(Pin::try_new(0, 0).unwrap(), Pin::new(0, 0));
+    assert_eq!(Pin::try_new(99, MAX_PIN).unwrap(), Pin::new(99, MAX_PIN));
+    let err = Pin::try_new(100, 4242).unwrap_err();
+    assert_eq!(err.to_string(), "Id is too large: 100 > 99");
+    let err = Pin::try_new(7, MAX_PIN + 1).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Pin 7 is too large: 1000000000 > 999999999"
+    );
+}
+
+#[test]
+fn custom_keystreams_round_trip() {
+    let keystream = |master: u32, id: u8| master.rotate_left(id as u32).wrapping_mul(0x9e37_79b9);
+    let mut rng = StepRng::new(0, 0);
+    for (id, pin) in [(0, 4242), (42, 0), (99, MAX_PIN)] {
+        let raw = pins::encrypt_with(&keystream, 1234, id, pin, &mut rng).unwrap();
+        assert_eq!(raw, keystream(1234, id) ^ pin);
+        assert_eq!(pins::decrypt_with(&keystream, 1234, id, raw), pin);
+        // The default keystream is the one of the vault format
+        let raw = pins::encrypt(1234, id, pin, &mut rng).unwrap();
+        assert_eq!(
+            raw,
+            pins::encrypt_with(&XorShift, 1234, id, pin, &mut rng).unwrap()
+        );
+    }
+}
+
+#[test]
+fn copies_keep_texts_history_and_times() {
+    let mut pins = Pins::new(1234);
+    pins.add(1111);
+    pins.set(0, 2222);
+    pins.set_expiry(0, Some(1_700_000_000));
+    pins.mark_used(0, 1_600_000_000);
+    pins.add_text("secret");
+    let mut copy = Pins::new(1234);
+    copy.add(3333);
+    assert!(copy.add_copy(&pins, 0));
+    assert!(copy.add_copy(&pins, 1));
+    assert_eq!(copy.get(1).pin, 2222);
+    assert_eq!(copy.history(1), [1111]);
+    assert_eq!(copy.expires(1), Some(1_700_000_000));
+    assert_eq!(copy.last_used(1), Some(1_600_000_000));
+    assert_eq!(copy.text(2), Some("secret"));
+    copy.validate().unwrap();
+}
@@ -0,0 +1,166 @@
+//! Round trips through `Pins::save`
+
+use rand::rngs::mock::StepRng;
+use vault_crypt::pins::{self, PinKind, Pins, HISTORY_LEN};
+
+#[test]
+fn empty_pins_are_not_counted() {
+    let mut pins = Pins::new(1234);
+    pins.add(0);
+    pins.add(4242);
+    pins.add(0);
+    let bytes = pins.save().unwrap();
+    assert_eq!(bytes[0], 1);
+    Pins::verify(&bytes).unwrap();
+    let loaded = Pins::load_checked(&bytes, 1234).unwrap();
+    assert_eq!(loaded.len(), 1);
+    assert_eq!(loaded.get(0).pin, 4242);
+}
+
+#[test]
+fn empty_pins_are_not_counted_with_metadata() {
+    let mut pins = Pins::new(1234);
+    pins.add(0);
+    pins.set_metadata("bank");
+    let bytes = pins.save().unwrap();
+    Pins::verify(&bytes).unwrap();
+    let loaded = Pins::load_checked(&bytes, 1234).unwrap();
+    assert!(loaded.is_empty());
+    assert_eq!(loaded.metadata(), "bank");
+}
+
+#[test]
+fn history_survives_saving() {
+    let mut pins = Pins::new(1234);
+    pins.add(1111);
+    for value in 2..=HISTORY_LEN as u32 + 2 {
+        pins.set(0, value);
+    }
+    let bytes = pins.save().unwrap();
+    assert_eq!(pins::format_version(&bytes).unwrap(), pins::VERSION_HISTORY);
+    let loaded = Pins::load_checked(&bytes, 1234).unwrap();
+    assert_eq!(loaded.history(0), pins.history(0));
+    assert_eq!(loaded.history(0).len(), HISTORY_LEN);
+    assert_eq!(loaded.history(0).last(), Some(&(HISTORY_LEN as u32 + 1)));
+}
+
+#[test]
+fn text_secrets_survive_saving() {
+    let mut pins = Pins::new(1234);
+    pins.add(4242);
+    pins.add_text("correct horse");
+    let bytes = pins.save().unwrap();
+    assert_eq!(pins::format_version(&bytes).unwrap(), pins::VERSION_TEXTS);
+    let loaded = Pins::load_checked(&bytes, 1234).unwrap();
+    assert!(loaded == pins);
+    assert_eq!(loaded.kind(1), PinKind::Text);
+    assert_eq!(loaded.text(1), Some("correct horse"));
+    assert_eq!(loaded.get(0).pin, 4242);
+}
+
+#[test]
+fn deterministic_saving_is_stable() {
+    let save = |pins: &Pins| pins.save_with(&mut StepRng::new(0, 0)).unwrap();
+    let mut pins = Pins::new(1234);
+    pins.add(4242);
+    pins.add(987_654_321);
+    pins.set(1, 13_579);
+    pins.add_text("correct horse");
+    pins.set_metadata("bank");
+    let bytes = save(&pins);
+    assert_eq!(save(&pins), bytes);
+    let loaded = Pins::load_checked(&bytes, 1234).unwrap();
+    assert!(loaded == pins);
+    assert_eq!(save(&loaded), bytes);
+    assert_eq!(loaded.history(1), [987_654_321]);
+}
+
+#[test]
+fn expiry_survives_saving() {
+    let mut pins = Pins::new(1234);
+    pins.add(4242);
+    pins.add(13_579);
+    pins.set_expiry(1, Some(1_700_000_000));
+    let bytes = pins.save().unwrap();
+    assert_eq!(pins::format_version(&bytes).unwrap(), pins::VERSION_EXPIRY);
+    Pins::verify(&bytes).unwrap();
+    let loaded = Pins::load_checked(&bytes, 1234).unwrap();
+    assert!(loaded == pins);
+    assert_eq!(loaded.expires(0), None);
+    assert_eq!(loaded.expires(1), Some(1_700_000_000));
+}
+
+#[test]
+fn last_use_survives_saving() {
+    let mut pins = Pins::new(1234);
+    pins.add(4242);
+    pins.add(13_579);
+    pins.mark_used(0, 1_700_000_000);
+    let bytes = pins.save().unwrap();
+    assert_eq!(pins::format_version(&bytes).unwrap(), pins::VERSION_USAGE);
+    Pins::verify(&bytes).unwrap();
+    let loaded = Pins::load_checked(&bytes, 1234).unwrap();
+    assert!(loaded == pins);
+    assert_eq!(loaded.last_used(0), Some(1_700_000_000));
+    assert_eq!(loaded.last_used(1), None);
+}
+
+#[test]
+fn swapped_values_follow_their_ids() {
+    let mut pins = Pins::new(1234);
+    pins.add(1111);
+    pins.add(2222);
+    pins.add_text("secret");
+    pins.set(0, 3333);
+    pins.set_expiry(0, Some(1_700_000_000));
+    pins.swap_ids(0, 2).unwrap();
+    assert!(pins.swap_ids(0, 3).is_err());
+    assert_eq!(pins.iter_ids().collect::<Vec<_>>(), [0, 1, 2]);
+    let loaded = Pins::load_checked(&pins.save().unwrap(), 1234).unwrap();
+    assert!(loaded == pins);
+    assert_eq!(loaded.text(0), Some("secret"));
+    assert_eq!(loaded.get(2).pin, 3333);
+    assert_eq!(loaded.history(2), [1111]);
+    assert_eq!(loaded.expires(2), Some(1_700_000_000));
+    assert_eq!(loaded.history(0), []);
+}
+
+#[test]
+fn text_secrets_keep_their_history() {
+    let mut pins = Pins::new(1234);
+    pins.add(4242);
+    pins.set(0, 13_579);
+    pins.set_text(0, "secret");
+    let loaded = Pins::load_checked(&pins.save().unwrap(), 1234).unwrap();
+    assert_eq!(loaded.text(0), Some("secret"));
+    assert_eq!(loaded.history(0), [4242]);
+}
+
+/// A random number generator that always fails
+struct BrokenRng;
+
+impl rand::RngCore for BrokenRng {
+    fn next_u32(&mut self) -> u32 {
+        unreachable!("only try_fill_bytes may be used")
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        unreachable!("only try_fill_bytes may be used")
+    }
+
+    fn fill_bytes(&mut self, _dest: &mut [u8]) {
+        unreachable!("only try_fill_bytes may be used")
+    }
+
+    fn try_fill_bytes(&mut self, _dest: &mut [u8]) -> Result<(), rand::Error> {
+        Err(rand::Error::new(std::io::Error::other("no entropy")))
+    }
+}
+
+#[test]
+fn failing_rng_is_an_error() {
+    let mut pins = Pins::new(1234);
+    pins.add(4242);
+    let err = pins.save_with(&mut BrokenRng).unwrap_err();
+    assert_eq!(err.to_string(), "Random number generator is unavailable");
+}
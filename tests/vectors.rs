@@ -0,0 +1,87 @@
+//! Known answers for the vault format
+//!
+//! Each vector pairs a master and its decrypted pins with the exact bytes of
+//! the vault. The two obfuscation bits of every pin are random when saving
+//! normally, the vectors are saved with them cleared.
+
+use rand::rngs::mock::StepRng;
+use vault_crypt::pins::{Pin, Pins};
+
+struct Vector {
+    master: u32,
+    pins: &'static [(u8, u32)],
+    metadata: &'static str,
+    bytes: &'static [u8],
+}
+
+const EMPTY: Vector = Vector {
+    master: 1234,
+    pins: &[],
+    metadata: "",
+    bytes: &[0x00],
+};
+
+const SINGLE_PIN: Vector = Vector {
+    master: 1234,
+    pins: &[(0, 4242)],
+    metadata: "",
+    bytes: &[0x01, 0x00, 0x13, 0xd2, 0xc7, 0xed],
+};
+
+const MAX_ID: Vector = Vector {
+    master: 987654321,
+    pins: &[(99, 123456789)],
+    metadata: "",
+    bytes: &[0x01, 0x63, 0xba, 0xc6, 0x84, 0xd0],
+};
+
+const METADATA: Vector = Vector {
+    master: 20240101,
+    pins: &[(0, 1)],
+    metadata: "test",
+    bytes: &[
+        0xff, 0x02, 0x01, 0x00, 0xe6, 0xef, 0x59, 0x30, 0x00, 0x04, 0xa6, 0x71, 0x75, 0x16,
+    ],
+};
+
+const VECTORS: [Vector; 4] = [EMPTY, SINGLE_PIN, MAX_ID, METADATA];
+
+/// Clears the obfuscation bits
+fn zero_rng() -> StepRng {
+    StepRng::new(0, 0)
+}
+
+#[test]
+fn load_vectors() {
+    for vector in &VECTORS {
+        Pins::verify(vector.bytes).unwrap();
+        let pins = Pins::load(vector.bytes, vector.master);
+        let expected: Vec<_> = vector
+            .pins
+            .iter()
+            .map(|&(id, pin)| Pin::new(id, pin))
+            .collect();
+        assert_eq!(pins.iter().copied().collect::<Vec<_>>(), expected);
+        assert_eq!(pins.metadata(), vector.metadata);
+    }
+}
+
+#[test]
+fn save_vectors() {
+    for vector in &VECTORS {
+        let pins = Pins::load(vector.bytes, vector.master);
+        assert_eq!(pins.save_with(&mut zero_rng()).unwrap(), vector.bytes);
+    }
+}
+
+#[test]
+fn build_vectors() {
+    let mut pins = Pins::new(SINGLE_PIN.master);
+    pins.add(4242);
+    assert_eq!(pins.save_with(&mut zero_rng()).unwrap(), SINGLE_PIN.bytes);
+
+    let mut pins = Pins::new(METADATA.master);
+    pins.add(1);
+    pins.set_metadata("test");
+    assert_eq!(pins.save_with(&mut zero_rng()).unwrap(), METADATA.bytes);
+}
@@ -0,0 +1,52 @@
+//! Structural checks of `Pins::verify` on damaged files
+
+use vault_crypt::{error::VaultError, pins::Pins};
+
+fn malformed_reason(bytes: &[u8]) -> String {
+    let err = Pins::verify(bytes).err().unwrap();
+    match err.downcast_ref::<VaultError>() {
+        Some(VaultError::Malformed(reason)) => reason.clone(),
+        _ => panic!("Expected a malformed vault, got: {err}"),
+    }
+}
+
+#[test]
+fn trailing_garbage_is_rejected() {
+    let mut pins = Pins::new(1234);
+    pins.add(4242);
+    let mut bytes = pins.save().unwrap();
+    Pins::verify(&bytes).unwrap();
+    bytes.extend([0xAB, 0xCD]);
+    assert_eq!(
+        malformed_reason(&bytes),
+        "2 trailing byte(s) after the pins"
+    );
+
+    pins.set_metadata("bank");
+    let mut bytes = pins.save().unwrap();
+    Pins::verify(&bytes).unwrap();
+    bytes.push(0);
+    assert_eq!(
+        malformed_reason(&bytes),
+        "1 trailing byte(s) after the metadata"
+    );
+}
+
+#[test]
+fn short_records_are_rejected() {
+    let mut pins = Pins::new(1234);
+    pins.add(4242);
+    pins.add(1337);
+    let bytes = pins.save().unwrap();
+    assert_eq!(
+        malformed_reason(&bytes[..bytes.len() - 1]),
+        "Not enough bytes for given length"
+    );
+
+    pins.set_metadata("bank");
+    let bytes = pins.save().unwrap();
+    assert_eq!(
+        malformed_reason(&bytes[..bytes.len() - 1]),
+        "Not enough bytes for metadata length"
+    );
+}